@@ -0,0 +1,31 @@
+//! Decoding of sketches of 32-bit elements filled up to capacity, with the backend decoder
+//! (`libminisketch` by default) and the subquadratic one.
+//!
+//! Run with `cargo bench --bench decode`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use minisketch_rs::{Decoder, Minisketch, SketchParams};
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.sample_size(10);
+
+    for &capacity in &[16, 64, 256, 1024, 4096] {
+        let elements = (1..=capacity as u32).map(|i| u64::from(i.wrapping_mul(0x9e37_79b9)));
+        let sketch =
+            Minisketch::from_iter_with(SketchParams::new(32, 0, capacity).unwrap(), elements)
+                .unwrap();
+        let mut buf = vec![0u64; capacity];
+
+        for &(name, decoder) in &[("backend", Decoder::Backend), ("fast", Decoder::Fast)] {
+            group.bench_with_input(BenchmarkId::new(name, capacity), &sketch, |b, sketch| {
+                b.iter(|| sketch.decode_with(&mut buf, decoder).unwrap())
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
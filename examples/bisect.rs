@@ -63,7 +63,7 @@
 //!     |<----------------------------------|
 //!     |                                   |
 //! ```
-use minisketch_rs::Minisketch;
+use minisketch_rs::{Minisketch, SketchParams};
 
 /// Extracts remainder sketch from a difference of two sketches
 fn sub_sketches(s1: &[u8], s2: &[u8], d: usize, seed: Option<u64>) -> Vec<u8> {
@@ -71,13 +71,13 @@ fn sub_sketches(s1: &[u8], s2: &[u8], d: usize, seed: Option<u64>) -> Vec<u8> {
     if let Some(seed) = seed {
         a.set_seed(seed);
     }
-    a.deserialize(s1);
+    a.deserialize(s1).expect("Deserialize sketch sub");
 
     let mut b = create_minisketch(d, seed);
     if let Some(seed) = seed {
         b.set_seed(seed);
     }
-    b.deserialize(s2);
+    b.deserialize(s2).expect("Deserialize sketch sub");
 
     a.merge(&b).expect("Sketch sub merge");
 
@@ -94,15 +94,18 @@ fn sketch_from_range(
     seed: Option<u64>,
 ) -> Minisketch {
     let mut sketch = create_minisketch(capacity, seed);
-    for i in range {
-        sketch.add(i);
-    }
+    sketch.extend(range);
     sketch
 }
 
-/// Creates `Minisketch` for given `capacity` and optional `seed`.
+/// Creates `Minisketch` of 64-bit elements for given `capacity` and optional `seed`.
 fn create_minisketch(capacity: usize, seed: Option<u64>) -> Minisketch {
-    let mut minisketch = Minisketch::try_new(64, 0, capacity).unwrap();
+    let params = SketchParams::builder()
+        .bits(64)
+        .capacity(capacity)
+        .build()
+        .expect("Sketch params");
+    let mut minisketch = params.create_sketch().expect("Create sketch");
 
     if let Some(seed) = seed {
         minisketch.set_seed(seed);
@@ -127,17 +130,14 @@ fn reconcile(
     seed: Option<u64>,
 ) -> Result<Vec<u64>, ()> {
     let mut a = create_minisketch(capacity, seed);
-    a.deserialize(sketch_a);
+    a.deserialize(sketch_a).map_err(|_| ())?;
 
     let mut b = create_minisketch(capacity, seed);
-    b.deserialize(sketch_b);
+    b.deserialize(sketch_b).map_err(|_| ())?;
 
     a.merge(&b).expect("Minisketch merge");
 
-    let mut diffs = vec![0u64; capacity];
-    let num_diffs = a.decode(&mut diffs).map_err(|_| ())?;
-
-    Ok(diffs.into_iter().take(num_diffs).collect())
+    a.decode_to_vec().map_err(|_| ())
 }
 
 fn example(capacity: usize) -> Result<Vec<u64>, ()> {
@@ -148,26 +148,21 @@ fn example(capacity: usize) -> Result<Vec<u64>, ()> {
     let b = 0..8;
 
     // Count difference between two sets
-    let set_diff = a.clone().into_iter().filter(|e| !b.contains(e)).count();
+    let set_diff = a.clone().filter(|e| !b.contains(e)).count();
 
-    println!(
-        "Alice's set: {:?}",
-        a.clone().into_iter().collect::<Vec<_>>()
-    );
-    println!("Bob's set: {:?}", b.clone().into_iter().collect::<Vec<_>>());
+    println!("Alice's set: {:?}", a.clone().collect::<Vec<_>>());
+    println!("Bob's set: {:?}", b.clone().collect::<Vec<_>>());
 
     // To increase chance of bisect success, take only even elements of the set,
     // so they're distributed uniformly.
     let b_half = b
         .clone()
-        .into_iter()
         .enumerate()
         .filter(|(i, _)| *i % 2 == 0)
         .map(|(_, n)| n)
         .collect::<Vec<_>>();
     let a_half = a
         .clone()
-        .into_iter()
         .enumerate()
         .filter(|(i, _)| *i % 2 == 0)
         .map(|(_, n)| n)
@@ -196,16 +191,9 @@ fn example(capacity: usize) -> Result<Vec<u64>, ()> {
         let res_1 = reconcile(&a_half, &b_half, capacity, seed);
         let res_2 = reconcile(&a_minus_a_2, &b_minus_b_2, capacity, seed);
 
-        let res = res_1.and_then(|diffs1| {
-            res_2.and_then(|diffs2| {
-                Ok(diffs1
-                    .into_iter()
-                    .chain(diffs2.into_iter())
-                    .collect::<Vec<_>>())
-            })
-        });
-
-        res
+        res_1.and_then(|diffs1| {
+            res_2.map(|diffs2| diffs1.into_iter().chain(diffs2).collect::<Vec<_>>())
+        })
     } else {
         Ok(simple.unwrap())
     }
@@ -7,6 +7,7 @@ main() {
     local examples=(
         simple
         bisect
+        extension
     )
 
     rm -rf src/examples
@@ -0,0 +1,285 @@
+use std::env;
+use std::fs;
+use std::fs::read_dir;
+use std::path::PathBuf;
+
+fn fail_on_empty_directory(name: &str) {
+    if fs::read_dir(name).map_or(true, |dir| dir.count() == 0) {
+        println!(
+            "The `{}` directory is empty, did you forget to pull the submodules?",
+            name
+        );
+        println!("Try `git submodule update --init --recursive`");
+        println!("Or link an installed library with the `system` feature or `MINISKETCH_LIB_DIR`");
+        panic!();
+    }
+}
+
+/// Installed `libminisketch` to link against instead of building the submodule.
+struct SystemLib {
+    link_dirs: Vec<PathBuf>,
+    libs: Vec<String>,
+    include_dirs: Vec<PathBuf>,
+    is_static: bool,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=minisketch/");
+    println!("cargo:rerun-if-changed=src/batch.c");
+    println!("cargo:rerun-if-env-changed=MINISKETCH_LIB_DIR");
+    println!("cargo:rerun-if-env-changed=MINISKETCH_INCLUDE_DIR");
+    println!("cargo:rerun-if-env-changed=MINISKETCH_STATIC");
+    println!("cargo:rerun-if-env-changed=CXXSTDLIB");
+
+    let system_lib = find_system_lib();
+    let include_dirs = match &system_lib {
+        Some(lib) => lib.include_dirs.clone(),
+        None => {
+            fail_on_empty_directory("minisketch");
+            let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+            vec![manifest_dir.join("minisketch/include")]
+        }
+    };
+
+    // Lets build scripts of dependent crates find `minisketch.h` in `DEP_MINISKETCH_INCLUDE`
+    if let Some(dir) = include_dirs.first() {
+        println!("cargo:include={}", dir.display());
+    }
+
+    // The batch helper depends on libminisketch, so it must be linked before it
+    build_batch(&include_dirs);
+    println!("cargo:rustc-check-cfg=cfg(minisketch_installed)");
+    match &system_lib {
+        Some(lib) => {
+            // Fields of an installed library don't follow the `bits-*` features
+            println!("cargo:rustc-cfg=minisketch_installed");
+            link_system_lib(lib);
+        }
+        None => build_lib(),
+    }
+    generate_bindings(&include_dirs);
+}
+
+/// Looks for an installed library in `MINISKETCH_LIB_DIR`, or with pkg-config if the `system`
+/// feature is on. Returns `None` if the bundled submodule should be built instead.
+fn find_system_lib() -> Option<SystemLib> {
+    let is_static = env::var_os("MINISKETCH_STATIC").is_some();
+    let include_dirs = env::var_os("MINISKETCH_INCLUDE_DIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    if let Some(lib_dir) = env::var_os("MINISKETCH_LIB_DIR") {
+        return Some(SystemLib {
+            link_dirs: vec![PathBuf::from(lib_dir)],
+            libs: vec!["minisketch".to_string()],
+            include_dirs,
+            is_static,
+        });
+    }
+
+    probe_pkg_config(include_dirs, is_static)
+}
+
+#[cfg(feature = "system")]
+fn probe_pkg_config(mut include_dirs: Vec<PathBuf>, is_static: bool) -> Option<SystemLib> {
+    // Link lines are printed later, after the batch helper
+    let library = pkg_config::Config::new()
+        .statik(is_static)
+        .cargo_metadata(false)
+        .probe("libminisketch")
+        .expect("Unable to find libminisketch with pkg-config");
+    include_dirs.extend(library.include_paths);
+
+    Some(SystemLib {
+        link_dirs: library.link_paths,
+        libs: library.libs,
+        include_dirs,
+        is_static,
+    })
+}
+
+#[cfg(not(feature = "system"))]
+fn probe_pkg_config(_include_dirs: Vec<PathBuf>, _is_static: bool) -> Option<SystemLib> {
+    None
+}
+
+fn link_system_lib(lib: &SystemLib) {
+    for dir in &lib.link_dirs {
+        println!("cargo:rustc-link-search=native={}", dir.display());
+    }
+
+    let kind = if lib.is_static { "static=" } else { "" };
+    for name in &lib.libs {
+        println!("cargo:rustc-link-lib={}{}", kind, name);
+    }
+
+    // A shared library brings its own C++ runtime, a static one needs it to be linked
+    if lib.is_static {
+        if let Some(runtime) = cpp_runtime() {
+            println!("cargo:rustc-link-lib={}", runtime);
+        }
+    }
+}
+
+/// Returns the C++ standard library for the target, following `cc` which links it for
+/// the bundled build. Can be overridden with `CXXSTDLIB`, where an empty value links nothing.
+fn cpp_runtime() -> Option<String> {
+    if let Ok(runtime) = env::var("CXXSTDLIB") {
+        return Some(runtime).filter(|runtime| !runtime.is_empty());
+    }
+
+    let target = env::var("TARGET").unwrap();
+    if target.contains("msvc") {
+        None
+    } else if target.contains("apple") || target.contains("freebsd") || target.contains("openbsd") {
+        Some("c++".to_string())
+    } else if target.contains("android") {
+        Some("c++_shared".to_string())
+    } else {
+        Some("stdc++".to_string())
+    }
+}
+
+fn build_lib() {
+    // CLMUL implementations are only compiled for x86, and only if the `clmul` feature is on
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+    let clmul = env::var_os("CARGO_FEATURE_CLMUL").is_some()
+        && (target_arch == "x86" || target_arch == "x86_64");
+
+    let field_bytes = selected_field_bytes();
+
+    // Collect minisketch.cpp and .cpp files of selected fields from fields/ directory
+    let fields = read_dir("minisketch/src/fields").unwrap().filter(|f| {
+        let name = f.as_ref().unwrap().file_name();
+        matches!(field_file_bytes(&name.to_string_lossy()), Some(n) if field_bytes.contains(&n))
+    });
+    let (clmul_files, src_files): (Vec<_>, Vec<_>) = read_dir("minisketch/src")
+        .unwrap()
+        .chain(fields)
+        .map(|f| f.unwrap())
+        .filter(|f| !f.file_name().to_string_lossy().contains("test-exhaust.cpp"))
+        .filter(|f| !f.file_name().to_string_lossy().contains("bench.cpp"))
+        .filter(|f| f.file_name().to_string_lossy().ends_with(".cpp"))
+        .map(|f| f.path())
+        .partition(|path| {
+            path.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("clmul_")
+        });
+
+    // Build minisketch library. It stays portable: with `HAVE_CLMUL` defined it checks CPUID
+    // before constructing a CLMUL sketch, and falls back to the generic implementation.
+    let mut build = cc::Build::new();
+    build
+        .files(src_files)
+        .cpp(true)
+        .opt_level(2)
+        .debug(false)
+        .warnings(false)
+        .extra_warnings(false)
+        .flag("-g0")
+        .flag("-std=c++11")
+        .define("HAVE_CLZ", None);
+    define_fields(&mut build, &field_bytes);
+    if clmul {
+        build.define("HAVE_CLMUL", None);
+    }
+    build.compile("libminisketch.a");
+
+    // Only CLMUL fields are compiled with `-mpclmul`. Link them after libminisketch that uses them.
+    if clmul {
+        let mut build = cc::Build::new();
+        build
+            .files(clmul_files)
+            .cpp(true)
+            .opt_level(2)
+            .debug(false)
+            .warnings(false)
+            .extra_warnings(false)
+            .flag("-mpclmul")
+            .flag("-g0")
+            .flag("-std=c++11")
+            .define("HAVE_CLZ", None)
+            .define("HAVE_CLMUL", None);
+        define_fields(&mut build, &field_bytes);
+        build.compile("libminisketch_clmul.a");
+    }
+}
+
+/// Returns the field sizes in bytes selected with `bits-1byte` to `bits-8byte` features.
+fn selected_field_bytes() -> Vec<u32> {
+    let field_bytes = (1..=8)
+        .filter(|n| env::var_os(format!("CARGO_FEATURE_BITS_{}BYTE", n)).is_some())
+        .collect::<Vec<_>>();
+
+    if field_bytes.is_empty() {
+        println!("No element sizes are selected, enable `all-bits` or some of `bits-1byte` to `bits-8byte` features");
+        panic!();
+    }
+
+    field_bytes
+}
+
+/// Parses the field size in bytes from a name like `generic_1byte.cpp` or `clmul_3bytes.cpp`.
+fn field_file_bytes(name: &str) -> Option<u32> {
+    let size = name.split('_').nth(1)?;
+    let digits = size.find(|c: char| !c.is_ascii_digit())?;
+    size[..digits].parse().ok()
+}
+
+/// Enables fields of selected sizes in `libminisketch`, which disables the rest of them.
+fn define_fields(build: &mut cc::Build, field_bytes: &[u32]) {
+    build.define("DISABLE_DEFAULT_FIELDS", None);
+    for &n in field_bytes {
+        for bits in (8 * n - 7).max(2)..=8 * n {
+            build.define(&format!("ENABLE_FIELD_INT_{}", bits), None);
+        }
+    }
+}
+
+fn build_batch(include_dirs: &[PathBuf]) {
+    cc::Build::new()
+        .file("src/batch.c")
+        .includes(include_dirs)
+        .opt_level(2)
+        .debug(false)
+        .warnings(false)
+        .extra_warnings(false)
+        .flag("-std=c99")
+        .compile("libminisketch_rs_batch.a")
+}
+
+/// Regenerates bindings from `minisketch.h` into `$OUT_DIR/bindings.rs`, to be used instead
+/// of the checked-in `src/bindings.rs`.
+#[cfg(feature = "bindgen")]
+fn generate_bindings(include_dirs: &[PathBuf]) {
+    let bindings = bindgen::Builder::default()
+        .generate_comments(false)
+        // The header is looked up in include directories, or in system ones if there are none
+        .header_contents("wrapper.h", "#include <minisketch.h>")
+        .clang_args(
+            include_dirs
+                .iter()
+                .map(|dir| format!("-I{}", dir.display())),
+        )
+        .whitelist_type("minisketch")
+        .opaque_type("minisketch")
+        // We'll redefine Clone, Copy and Drop by utilizing minisketch_clone() and minisketch_destroy()
+        .no_copy("minisketch")
+        .whitelist_function("minisketch_.+") // Bind to all minisketch_...() functions
+        .size_t_is_usize(true)
+        .generate()
+        .expect("Unable to generate bindings");
+
+    // Write the bindings to the $OUT_DIR/bindings.rs file.
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+    bindings
+        .write_to_file(out_path.join("bindings.rs"))
+        .expect("Couldn't write bindings!");
+}
+
+#[cfg(not(feature = "bindgen"))]
+fn generate_bindings(_include_dirs: &[PathBuf]) {}
@@ -0,0 +1,11 @@
+#include <stddef.h>
+#include <stdint.h>
+
+#include "minisketch.h"
+
+/* Adds `len` elements to a sketch with a single call from Rust. */
+void minisketch_rs_add_uint64_batch(minisketch* sketch, const uint64_t* elements, size_t len) {
+    for (size_t i = 0; i < len; ++i) {
+        minisketch_add_uint64(sketch, elements[i]);
+    }
+}
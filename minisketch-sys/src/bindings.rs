@@ -0,0 +1,56 @@
+/* automatically generated by rust-bindgen 0.55.1 */
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct minisketch {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn minisketch_bits_supported(bits: u32) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn minisketch_implementation_max() -> u32;
+}
+extern "C" {
+    pub fn minisketch_create(bits: u32, implementation: u32, capacity: usize) -> *mut minisketch;
+}
+extern "C" {
+    pub fn minisketch_bits(sketch: *const minisketch) -> u32;
+}
+extern "C" {
+    pub fn minisketch_capacity(sketch: *const minisketch) -> usize;
+}
+extern "C" {
+    pub fn minisketch_implementation(sketch: *const minisketch) -> u32;
+}
+extern "C" {
+    pub fn minisketch_set_seed(sketch: *mut minisketch, seed: u64);
+}
+extern "C" {
+    pub fn minisketch_clone(sketch: *const minisketch) -> *mut minisketch;
+}
+extern "C" {
+    pub fn minisketch_destroy(sketch: *mut minisketch);
+}
+extern "C" {
+    pub fn minisketch_serialized_size(sketch: *const minisketch) -> usize;
+}
+extern "C" {
+    pub fn minisketch_serialize(sketch: *const minisketch, output: *mut ::std::os::raw::c_uchar);
+}
+extern "C" {
+    pub fn minisketch_deserialize(sketch: *mut minisketch, input: *const ::std::os::raw::c_uchar);
+}
+extern "C" {
+    pub fn minisketch_add_uint64(sketch: *mut minisketch, element: u64);
+}
+extern "C" {
+    pub fn minisketch_merge(sketch: *mut minisketch, other_sketch: *const minisketch) -> usize;
+}
+extern "C" {
+    pub fn minisketch_decode(
+        sketch: *const minisketch,
+        max_elements: usize,
+        output: *mut u64,
+    ) -> isize;
+}
@@ -0,0 +1,57 @@
+#![allow(non_upper_case_globals)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![doc(html_root_url = "https://docs.rs/minisketch-sys/0.1.0")]
+
+//! # minisketch-sys
+//!
+//! Raw FFI bindings to [minisketch], a C++ library by [Pieter Wuille] for efficient set
+//! reconciliation. See [minisketch-rs] for a safe wrapper.
+//!
+//! By default, the bundled `minisketch` submodule is built and linked. Cargo features and
+//! environment variables that control the build are described in the README.
+//!
+//! Build scripts of dependent crates can find `minisketch.h` in the directory from the
+//! `DEP_MINISKETCH_INCLUDE` environment variable.
+//!
+//! [minisketch]: https://github.com/sipa/minisketch
+//! [Pieter Wuille]: https://github.com/sipa
+//! [minisketch-rs]: https://docs.rs/minisketch-rs
+
+#[cfg(feature = "bindgen")]
+include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+#[cfg(not(feature = "bindgen"))]
+include!("bindings.rs");
+
+/// `true` if an installed `libminisketch` is linked instead of the bundled one, with the `system`
+/// feature or `MINISKETCH_LIB_DIR`. Its fields don't depend on the `bits-*` features then.
+pub const INSTALLED_LIB: bool = cfg!(minisketch_installed);
+
+// Helper that is compiled from `src/batch.c`, not a part of `libminisketch`
+extern "C" {
+    /// Adds `len` elements starting at `elements` to a sketch, as if by calling
+    /// `minisketch_add_uint64` for each of them.
+    pub fn minisketch_rs_add_uint64_batch(
+        sketch: *mut minisketch,
+        elements: *const u64,
+        len: usize,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(feature = "bindgen")]
+    pub fn checked_in_bindings() {
+        // Formatting depends on whether rustfmt is installed, so compare without whitespace
+        let strip = |bindings: &str| bindings.split_whitespace().collect::<String>();
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+        let checked_in = include_str!("bindings.rs");
+
+        assert!(
+            strip(generated) == strip(checked_in),
+            "src/bindings.rs is out of date with minisketch.h, replace it with {}/bindings.rs",
+            env!("OUT_DIR")
+        );
+    }
+}
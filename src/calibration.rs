@@ -0,0 +1,112 @@
+//! Choosing the fastest implementation for an element size on the running host.
+
+use crate::Minisketch;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::sync::{Mutex, PoisonError};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+/// Capacity of sketches used for benchmarking.
+#[cfg(feature = "std")]
+const BENCH_CAPACITY: usize = 16;
+
+/// Number of benchmark rounds per implementation. The fastest round is taken, to filter out noise.
+#[cfg(feature = "std")]
+const BENCH_ROUNDS: usize = 3;
+
+/// Implementations chosen so far, keyed by element size.
+#[cfg(feature = "std")]
+static BEST_IMPLEMENTATIONS: Mutex<BTreeMap<u32, Option<u32>>> = Mutex::new(BTreeMap::new());
+
+impl Minisketch {
+    /// Lists every `(bits, implementation)` combination that `libminisketch` supports on the
+    /// running host.
+    ///
+    /// Implementation 0 is always available for supported element sizes, while others rely on
+    /// CLMUL instructions. They are only listed when the `clmul` feature is on and the CPU
+    /// supports them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let configurations = Minisketch::supported_configurations();
+    /// assert!(configurations.contains(&(64, 0)));
+    /// ```
+    pub fn supported_configurations() -> Vec<(u32, u32)> {
+        (1..=64)
+            .filter(|&bits| Self::bits_supported(bits))
+            .flat_map(|bits| Self::implementations_for(bits).map(move |imp| (bits, imp)))
+            .collect()
+    }
+
+    /// Returns the fastest implementation for sketches of `bits`-bit elements on the running
+    /// host, or `None` if no implementation supports such elements.
+    ///
+    /// On the first call for given `bits`, every available implementation is benchmarked by
+    /// adding elements to a small sketch and decoding it. The winner is cached for the rest of
+    /// the process, so later calls are cheap. Benchmarking needs a clock, so this requires the
+    /// `std` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let implementation = Minisketch::best_implementation(32).unwrap();
+    /// let sketch = Minisketch::try_new(32, implementation, 16)?;
+    ///
+    /// assert_eq!(Minisketch::best_implementation(65), None);
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn best_implementation(bits: u32) -> Option<u32> {
+        // Holding the lock while benchmarking keeps concurrent callers from running it twice
+        let mut best = BEST_IMPLEMENTATIONS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        *best.entry(bits).or_insert_with(|| {
+            let implementations = Self::implementations_for(bits).collect::<Vec<_>>();
+            if implementations.len() > 1 {
+                implementations
+                    .into_iter()
+                    .min_by_key(|&implementation| benchmark(bits, implementation))
+            } else {
+                implementations.first().copied()
+            }
+        })
+    }
+
+    /// Iterates over implementations that support `bits`-bit elements.
+    fn implementations_for(bits: u32) -> impl Iterator<Item = u32> {
+        (0..=Self::implementation_max())
+            .filter(move |&implementation| Self::try_new(bits, implementation, 1).is_ok())
+    }
+}
+
+/// Measures the fastest of several rounds of filling up a sketch and decoding it.
+#[cfg(feature = "std")]
+fn benchmark(bits: u32, implementation: u32) -> Duration {
+    let mask = u64::MAX >> (64 - bits);
+
+    (0..BENCH_ROUNDS)
+        .map(|round| {
+            let start = Instant::now();
+
+            if let Ok(mut sketch) = Minisketch::try_new(bits, implementation, BENCH_CAPACITY) {
+                for i in 0..BENCH_CAPACITY {
+                    let seed = (round * BENCH_CAPACITY + i + 1) as u64;
+                    sketch.add((seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) & mask) | 1);
+                }
+                // Small fields may not fit all elements, but the time spent still counts
+                let _ = sketch.decode_to_vec();
+            }
+
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
@@ -0,0 +1,681 @@
+//! Decoding algorithms, and a subquadratic decoder for sketches of large capacity.
+//!
+//! Decoding finds the shortest linear recurrence that generates the power sums of a sketch, whose
+//! characteristic polynomial has the elements as roots. The backend decoder does it with the
+//! Berlekamp-Massey algorithm and finds roots with polynomial arithmetic of quadratic cost.
+//!
+//! For `n = 2c` power sums `s_1, ..., s_n`, a monic polynomial `L` of degree `d` is the
+//! characteristic polynomial of a recurrence that generates them iff `L * A mod x^n` has degree
+//! below `d`, where `A = s_1 * x^(n-1) + s_2 * x^(n-2) + ... + s_n`. The extended Euclidean
+//! algorithm on `x^n` and `A` yields cofactors `t_i` with `t_i * A = r_i mod x^n`, and the first
+//! `t_i` with `deg r_i < deg t_i` is the shortest such polynomial (J. L. Dornstetter, "On the
+//! equivalence between Berlekamp's and Euclid's algorithms", 1987). It's unique if its degree is
+//! at most `c`, so Berlekamp-Massey finds the same one. The half-GCD algorithm jumps to that step
+//! with a logarithmic number of polynomial products. Longer recurrences are only decoded into
+//! buffers larger than the capacity, and then Berlekamp-Massey is used to pick the same one.
+//!
+//! Roots are then split out in the same order as in the pure-Rust backend, by gcds with traces of
+//! `b * x` for basis elements `b`. Traces are combined from powers `x^(2^i)` that the check for
+//! distinct roots computes anyway, and are reduced modulo factors as they split, in a batch for
+//! all the splits ahead. Products use the Karatsuba algorithm and division uses Newton iteration,
+//! so the cost of decoding grows as about `c^1.58` times logarithmic factors, instead of `c^2`.
+
+use crate::field::Field;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Polynomials shorter than this are multiplied with the schoolbook algorithm.
+const KARATSUBA_THRESHOLD: usize = 32;
+
+/// Quotients shorter than this are computed with long division.
+const NEWTON_THRESHOLD: usize = 64;
+
+/// Polynomials shorter than this are reduced with plain Euclidean steps in half-GCD.
+const HALF_GCD_THRESHOLD: usize = 64;
+
+/// Algorithm used to decode a sketch.
+///
+/// Both decoders produce the same elements and fail on the same sketches, so the choice only
+/// affects speed. Elements may come in a different order, though.
+///
+/// # Examples
+///
+/// ```rust
+/// use minisketch_rs::{Decoder, Minisketch};
+/// let mut sketch = Minisketch::try_new(32, 0, 512)?;
+/// for element in 1..=300 {
+///     sketch.add(element * 12345);
+/// }
+///
+/// let mut elements = vec![0u64; sketch.capacity()];
+/// let num_elements = sketch.decode_with(&mut elements, Decoder::Fast)?;
+/// let mut decoded = elements[..num_elements].to_vec();
+/// decoded.sort_unstable();
+/// assert_eq!(decoded, sketch.decode_sorted()?);
+/// # Ok::<(), minisketch_rs::MinisketchError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Decoder {
+    /// Decoding of the sketch backend: `libminisketch`, or its port with the `pure-rust` feature.
+    /// Its cost grows quadratically with the number of elements, and it's the fastest choice for
+    /// capacities up to about a hundred.
+    #[default]
+    Backend,
+
+    /// Subquadratic decoding in Rust, with a half-GCD in place of Berlekamp-Massey and root
+    /// finding with fast polynomial arithmetic. It's the fastest choice for capacities in the
+    /// hundreds and above.
+    Fast,
+}
+
+/// Decodes a sketch of `bits`-bit elements from its serialization into `elements`, the same way
+/// as the pure-Rust backend does. Returns the number of decoded elements, or `None` if decoding
+/// failed.
+pub(crate) fn decode(
+    bits: u32,
+    capacity: usize,
+    serialized: &[u8],
+    elements: &mut [u64],
+) -> Option<usize> {
+    let field = Field::new(bits)?;
+    let syndromes = all_syndromes(&field, &odd_syndromes(&field, capacity, serialized));
+
+    let locator = locator(&field, &syndromes, elements.len())?;
+    let roots = find_roots(&field, &locator)?;
+    elements[..roots.len()].copy_from_slice(&roots);
+
+    Some(roots.len())
+}
+
+/// Reads `capacity` syndromes of `bits` bits each, in the LSB-first order.
+fn odd_syndromes(field: &Field, capacity: usize, serialized: &[u8]) -> Vec<u64> {
+    let bits = field.bits();
+    let mut bytes = serialized.iter();
+    let mut pending = 0u128;
+    let mut pending_bits = 0;
+
+    (0..capacity)
+        .map(|_| {
+            while pending_bits < bits {
+                pending |= u128::from(*bytes.next().unwrap()) << pending_bits;
+                pending_bits += 8;
+            }
+            let syndrome = field.truncate(pending as u64);
+            pending >>= bits;
+            pending_bits -= bits;
+
+            syndrome
+        })
+        .collect()
+}
+
+/// Returns power sums `s_1, s_2, ..., s_2c`, reconstructing even ones from odd ones.
+fn all_syndromes(field: &Field, odd: &[u64]) -> Vec<u64> {
+    let mut all = vec![0; 2 * odd.len()];
+    for (i, &syndrome) in odd.iter().enumerate() {
+        all[2 * i] = syndrome;
+    }
+    // s_(2i + 2) = s_(i + 1)^2, where s_(i + 1) is already known
+    for i in 0..odd.len() {
+        all[2 * i + 1] = field.sqr(all[i]);
+    }
+
+    all
+}
+
+/// Returns the number of elements that the locator polynomial of a sketch of `bits`-bit elements
+/// has as roots, i.e. its degree, or `None` if `bits` is unsupported.
+///
+/// Degree of the locator is the length of the shortest linear recurrence that generates the power
+/// sums, and the locator of the actual elements generates them as well. So the degree is a lower
+/// bound on the number of elements in the sketch, even when it can't be decoded.
+pub(crate) fn locator_degree(bits: u32, capacity: usize, serialized: &[u8]) -> Option<usize> {
+    let field = Field::new(bits)?;
+    let syndromes = all_syndromes(&field, &odd_syndromes(&field, capacity, serialized));
+
+    Some(shortest_recurrence(&field, &syndromes).0)
+}
+
+/// Finds the characteristic polynomial of the shortest linear recurrence that generates
+/// `syndromes`, i.e. the reverse of the error locator polynomial, which is monic.
+///
+/// Returns `None` if its degree exceeds `max_degree`, or if it has a root at 0, since then it
+/// can't be a locator of nonzero elements.
+fn locator(field: &Field, syndromes: &[u64], max_degree: usize) -> Option<Vec<u64>> {
+    let (degree, characteristic) = shortest_recurrence(field, syndromes);
+    if degree > max_degree {
+        return None;
+    }
+
+    let mut characteristic = match characteristic {
+        Some(characteristic) => characteristic,
+        None => {
+            // Longer recurrences are only decodable into buffers larger than the capacity, where
+            // Berlekamp-Massey picks one of them
+            let mut locator = berlekamp_massey(field, syndromes, max_degree)?;
+            locator.reverse();
+            return Some(locator);
+        }
+    };
+    if characteristic[0] == 0 {
+        return None;
+    }
+    make_monic(field, &mut characteristic);
+
+    Some(characteristic)
+}
+
+/// Returns the length of the shortest linear recurrence that generates `syndromes`, and its
+/// characteristic polynomial if it's unique, i.e. if its length is at most half the number of
+/// syndromes. The polynomial isn't necessarily monic.
+fn shortest_recurrence(field: &Field, syndromes: &[u64]) -> (usize, Option<Vec<u64>>) {
+    let n = syndromes.len();
+    let mut sequence = syndromes.iter().rev().copied().collect::<Vec<_>>();
+    trim(&mut sequence);
+    if sequence.is_empty() {
+        return (0, Some(vec![1]));
+    }
+
+    let mut power = vec![0; n + 1];
+    power[n] = 1;
+
+    // Half-GCD stops at the first remainder of degree below n / 2, and the recurrence is either
+    // found by then or one step later
+    let matrix = half_gcd(field, &power, &sequence);
+    let [_, next_remainder] = apply(field, &matrix, &power, &sequence);
+    let [_, next_cofactor] = &matrix[1];
+
+    if next_remainder.len() >= next_cofactor.len() {
+        // The recurrence is longer than n / 2, so it's no longer unique
+        return (n - (next_remainder.len() - 1), None);
+    }
+
+    (next_cofactor.len() - 1, Some(next_cofactor.clone()))
+}
+
+/// Finds the shortest linear recurrence that generates `syndromes`, i.e. the error locator
+/// polynomial, with coefficients from the lowest degree.
+///
+/// Returns `None` if its degree exceeds `max_degree`, or if its highest coefficient is 0, since
+/// then it can't be a locator of nonzero elements.
+pub(crate) fn berlekamp_massey(
+    field: &Field,
+    syndromes: &[u64],
+    max_degree: usize,
+) -> Option<Vec<u64>> {
+    let mut current = vec![1];
+    let mut prev = vec![1];
+    let mut prev_discrepancy = 1;
+
+    for n in 0..syndromes.len() {
+        let discrepancy = (1..current.len()).fold(syndromes[n], |discrepancy, i| {
+            discrepancy ^ field.mul(syndromes[n - i], current[i])
+        });
+        if discrepancy == 0 {
+            continue;
+        }
+
+        // Distance from the step at which `prev` was current
+        let shift = n + 1 - (current.len() - 1) - (prev.len() - 1);
+        let swap = 2 * (current.len() - 1) <= n;
+        let replaced = if swap {
+            if prev.len() + shift - 1 > max_degree {
+                return None;
+            }
+            let replaced = current.clone();
+            current.resize(prev.len() + shift, 0);
+            Some(replaced)
+        } else {
+            None
+        };
+
+        let factor = field.mul(discrepancy, field.inv(prev_discrepancy));
+        for (i, &coefficient) in prev.iter().enumerate() {
+            current[i + shift] ^= field.mul(factor, coefficient);
+        }
+
+        if let Some(replaced) = replaced {
+            prev = replaced;
+            prev_discrepancy = discrepancy;
+        }
+    }
+
+    if current.last() == Some(&0) {
+        return None;
+    }
+
+    Some(current)
+}
+
+/// Returns all roots of a monic polynomial, or `None` unless it has as many distinct roots as
+/// its degree.
+fn find_roots(field: &Field, poly: &[u64]) -> Option<Vec<u64>> {
+    let degree = poly.len() - 1;
+    let mut roots = Vec::with_capacity(degree);
+    if degree == 0 {
+        return Some(roots);
+    }
+
+    // x^(2^bits) - x is the product of (x - a) over all field elements, so the polynomial
+    // divides it, i.e. x^(2^bits) = x modulo the polynomial, iff its roots are distinct and in
+    // the field
+    let modulus = Modulus::new(field, poly);
+    let mut powers = vec![modulus.rem(vec![0, 1])];
+    for i in 0..field.bits() as usize {
+        powers.push(modulus.rem(sqr(field, &powers[i])));
+    }
+    if powers.pop() != powers.first().cloned() {
+        return None;
+    }
+
+    // Tr(b * x) = b * x + b^2 * x^2 + ... + b^(2^(bits - 1)) * x^(2^(bits - 1)) is a linear
+    // combination of the powers of x above, which is much cheaper than squaring it anew
+    let traces = (0..lookahead(degree).min(field.bits()))
+        .map(|basis| {
+            let mut b = 1 << basis;
+            powers.iter().fold(Vec::new(), |trace, power| {
+                let term = power.iter().map(|&p| field.mul(b, p)).collect::<Vec<_>>();
+                b = field.sqr(b);
+                add(&trace, &term)
+            })
+        })
+        .collect();
+
+    split_roots(field, poly.to_vec(), 0, traces, &mut roots);
+    Some(roots)
+}
+
+/// Returns the number of traces to prepare for splitting a polynomial of `degree`, which is
+/// enough to split it completely unless some basis elements fail to split its factors.
+fn lookahead(degree: usize) -> u32 {
+    if degree < 2 {
+        0
+    } else {
+        2 + usize::BITS - (degree - 1).leading_zeros()
+    }
+}
+
+/// Collects roots of a monic polynomial with distinct roots, splitting it by traces of
+/// `b * x` for basis elements `b = 2^i`, starting from `i = basis`.
+///
+/// Trace is a linear map onto `{0, 1}`, and every two distinct roots have different traces for
+/// some basis element. The gcd with `Tr(b * x)` holds the roots of zero trace, so it splits the
+/// polynomial unless all roots have the same trace, which then holds for both factors as well.
+///
+/// `traces` holds `Tr(b * x)` modulo the polynomial for the first few basis elements, and the
+/// rest are computed when needed. Factors get the remaining ones modulo themselves.
+fn split_roots(
+    field: &Field,
+    poly: Vec<u64>,
+    basis: u32,
+    traces: Vec<Vec<u64>>,
+    roots: &mut Vec<u64>,
+) {
+    if poly.len() == 2 {
+        roots.push(poly[0]);
+        return;
+    }
+
+    let modulus = Modulus::new(field, &poly);
+    let mut traces = traces.into_iter();
+    for basis in basis..field.bits() {
+        let trace = traces.next().unwrap_or_else(|| {
+            let mut power = vec![0, 1 << basis];
+            let mut trace = power.clone();
+            for _ in 1..field.bits() {
+                power = modulus.rem(sqr(field, &power));
+                trace = add(&trace, &power);
+            }
+            trace
+        });
+
+        let mut factor = gcd(field, poly.clone(), trace);
+        if factor.len() > 1 && factor.len() < poly.len() {
+            make_monic(field, &mut factor);
+            let (quotient, _) = div_rem(field, &poly, &factor);
+            let traces = traces.collect::<Vec<_>>();
+
+            for factor in [factor, quotient] {
+                let modulus = Modulus::with_quotient_len(field, &factor, poly.len() - factor.len());
+                let factor_traces = traces
+                    .iter()
+                    .take(lookahead(factor.len() - 1) as usize)
+                    .map(|trace| modulus.rem(trace.clone()))
+                    .collect();
+                split_roots(field, factor.clone(), basis + 1, factor_traces, roots);
+            }
+            return;
+        }
+    }
+
+    unreachable!("Distinct roots are always split by some basis element");
+}
+
+/// Removes leading zero coefficients, so that the zero polynomial is empty.
+fn trim(poly: &mut Vec<u64>) {
+    while poly.last() == Some(&0) {
+        let _ = poly.pop();
+    }
+}
+
+/// Makes a nonzero polynomial monic.
+fn make_monic(field: &Field, poly: &mut [u64]) {
+    let inverse = field.inv(*poly.last().unwrap());
+    for coefficient in poly.iter_mut() {
+        *coefficient = field.mul(*coefficient, inverse);
+    }
+}
+
+/// Returns `a + b`, which is also `a - b`.
+fn add(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = long.to_vec();
+    for (s, &b) in sum.iter_mut().zip(short) {
+        *s ^= b;
+    }
+    trim(&mut sum);
+
+    sum
+}
+
+/// Returns `a * b`.
+fn mul(field: &Field, a: &[u64], b: &[u64]) -> Vec<u64> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+
+    let mut product = vec![0; a.len() + b.len() - 1];
+    mul_add(field, a, b, &mut product);
+    trim(&mut product);
+
+    product
+}
+
+/// Adds `a * b` to `out`, which holds at least `a.len() + b.len() - 1` coefficients, with the
+/// Karatsuba algorithm.
+fn mul_add(field: &Field, a: &[u64], b: &[u64], out: &mut [u64]) {
+    let (a, b) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    if b.is_empty() {
+        return;
+    }
+
+    if b.len() < KARATSUBA_THRESHOLD {
+        for (k, out) in out[..a.len() + b.len() - 1].iter_mut().enumerate() {
+            // Coefficient of x^k sums a[i] * b[k - i] over valid i
+            let first = k.saturating_sub(b.len() - 1);
+            let last = k.min(a.len() - 1);
+            *out ^= field.dot_reversed(&a[first..=last], &b[k - last..=k - first]);
+        }
+        return;
+    }
+
+    let half = a.len().div_ceil(2);
+    if b.len() <= half {
+        // Unbalanced operands are multiplied piecewise, with pieces of `a` as long as `b`
+        for (i, piece) in a.chunks(b.len()).enumerate() {
+            mul_add(field, piece, b, &mut out[i * b.len()..]);
+        }
+        return;
+    }
+
+    // (a0 + a1 * x^h) * (b0 + b1 * x^h) =
+    //     a0 * b0 + ((a0 + a1) * (b0 + b1) - a0 * b0 - a1 * b1) * x^h + a1 * b1 * x^2h
+    let (a0, a1) = a.split_at(half);
+    let (b0, b1) = b.split_at(half);
+    let mut low = vec![0; 2 * half - 1];
+    mul_add(field, a0, b0, &mut low);
+    let mut high = vec![0; a1.len() + b1.len() - 1];
+    mul_add(field, a1, b1, &mut high);
+
+    let mut middle = vec![0; 2 * half - 1];
+    let a_sum = add_padded(a0, a1);
+    let b_sum = add_padded(b0, b1);
+    mul_add(field, &a_sum, &b_sum, &mut middle);
+    for (i, m) in middle.iter_mut().enumerate() {
+        *m ^= low[i] ^ high.get(i).copied().unwrap_or(0);
+    }
+
+    for (out, &l) in out.iter_mut().zip(&low) {
+        *out ^= l;
+    }
+    for (out, &m) in out[half..].iter_mut().zip(&middle) {
+        *out ^= m;
+    }
+    for (out, &h) in out[2 * half..].iter_mut().zip(&high) {
+        *out ^= h;
+    }
+}
+
+/// Returns `a + b` for `a` at least as long as `b`, keeping the length of `a`.
+fn add_padded(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let mut sum = a.to_vec();
+    for (s, &b) in sum.iter_mut().zip(b) {
+        *s ^= b;
+    }
+
+    sum
+}
+
+/// Returns `a^2`.
+fn sqr(field: &Field, a: &[u64]) -> Vec<u64> {
+    // Squaring is linear in characteristic 2, so only the coefficients are squared
+    let mut square = vec![0; (2 * a.len()).saturating_sub(1)];
+    for (i, &coefficient) in a.iter().enumerate() {
+        square[2 * i] = field.sqr(coefficient);
+    }
+
+    square
+}
+
+/// Returns the inverse of `a` modulo `x^len`, where `a` has a nonzero constant term.
+fn inverse_series(field: &Field, a: &[u64], len: usize) -> Vec<u64> {
+    // Newton iteration h' = 2h - a * h^2 doubles the number of correct coefficients, and the
+    // first term vanishes in characteristic 2
+    let mut inverse = vec![field.inv(a[0])];
+    let mut known = 1;
+    while known < len {
+        known = (2 * known).min(len);
+        inverse = mul(field, &a[..known.min(a.len())], &sqr(field, &inverse));
+        inverse.truncate(known);
+    }
+
+    inverse
+}
+
+/// Returns `x^len * a(1 / x)`, reversing the first `len` coefficients of `a`.
+fn reverse(a: &[u64], len: usize) -> Vec<u64> {
+    let mut reversed = a[..len.min(a.len())].to_vec();
+    reversed.resize(len, 0);
+    reversed.reverse();
+
+    reversed
+}
+
+/// Nonzero polynomial to divide by, with its reverse inverted for fast division.
+struct Modulus<'a> {
+    field: &'a Field,
+    poly: &'a [u64],
+    quotient_len: usize,
+    reverse_inverse: Vec<u64>,
+}
+
+impl<'a> Modulus<'a> {
+    /// Prepares division of polynomials shorter than `2 * poly.len() - 1` by `poly`.
+    fn new(field: &'a Field, poly: &'a [u64]) -> Self {
+        Self::with_quotient_len(field, poly, poly.len() - 1)
+    }
+
+    /// Prepares division by `poly` with quotients shorter than `quotient_len`.
+    fn with_quotient_len(field: &'a Field, poly: &'a [u64], quotient_len: usize) -> Self {
+        let reverse_inverse = if quotient_len >= NEWTON_THRESHOLD {
+            inverse_series(field, &reverse(poly, poly.len()), quotient_len)
+        } else {
+            Vec::new()
+        };
+
+        Modulus {
+            field,
+            poly,
+            quotient_len,
+            reverse_inverse,
+        }
+    }
+
+    /// Returns the quotient and the remainder of `a`.
+    fn div_rem(&self, mut a: Vec<u64>) -> (Vec<u64>, Vec<u64>) {
+        let field = self.field;
+        let degree = self.poly.len() - 1;
+        trim(&mut a);
+        if a.len() <= degree {
+            return (Vec::new(), a);
+        }
+
+        let quotient_len = a.len() - degree;
+        let mut quotient = if quotient_len >= NEWTON_THRESHOLD && quotient_len <= self.quotient_len
+        {
+            // Reversed, a = q * m + r turns into rev(a) = rev(q) * rev(m) mod x^(len q), where
+            // rev(m) is invertible as a series
+            let reversed = mul(
+                field,
+                &reverse(&a[degree..], quotient_len),
+                &self.reverse_inverse[..quotient_len.min(self.reverse_inverse.len())],
+            );
+            reverse(&reversed, quotient_len)
+        } else {
+            let inverse = field.inv(self.poly[degree]);
+            let mut remainder = a.clone();
+            let mut quotient = vec![0; quotient_len];
+            for i in (0..quotient_len).rev() {
+                let coefficient = field.mul(remainder[i + degree], inverse);
+                quotient[i] = coefficient;
+                if coefficient != 0 {
+                    for (r, &m) in remainder[i..].iter_mut().zip(self.poly) {
+                        *r ^= field.mul(coefficient, m);
+                    }
+                }
+            }
+            quotient
+        };
+        trim(&mut quotient);
+
+        let product = mul(field, &quotient, self.poly);
+        a.truncate(degree);
+        let remainder = add(&a, &product[..degree.min(product.len())]);
+
+        (quotient, remainder)
+    }
+
+    /// Returns the remainder of `a`.
+    fn rem(&self, a: Vec<u64>) -> Vec<u64> {
+        self.div_rem(a).1
+    }
+}
+
+/// Returns the quotient and the remainder of `a` divided by a nonzero polynomial `b`.
+fn div_rem(field: &Field, a: &[u64], b: &[u64]) -> (Vec<u64>, Vec<u64>) {
+    let quotient_len = (a.len() + 1).saturating_sub(b.len());
+    Modulus::with_quotient_len(field, b, quotient_len).div_rem(a.to_vec())
+}
+
+/// Matrix of polynomials that maps a pair of consecutive remainders of the Euclidean algorithm
+/// to a later one.
+type Matrix = [[Vec<u64>; 2]; 2];
+
+fn identity() -> Matrix {
+    [[vec![1], Vec::new()], [Vec::new(), vec![1]]]
+}
+
+/// Returns `m * (a, b)`.
+fn apply(field: &Field, m: &Matrix, a: &[u64], b: &[u64]) -> [Vec<u64>; 2] {
+    let [first, second] = m;
+    [
+        add(&mul(field, &first[0], a), &mul(field, &first[1], b)),
+        add(&mul(field, &second[0], a), &mul(field, &second[1], b)),
+    ]
+}
+
+/// Returns `m * n`.
+fn mat_mul(field: &Field, m: &Matrix, n: &Matrix) -> Matrix {
+    let entry = |i: usize, j: usize| {
+        add(
+            &mul(field, &m[i][0], &n[0][j]),
+            &mul(field, &m[i][1], &n[1][j]),
+        )
+    };
+
+    [[entry(0, 0), entry(0, 1)], [entry(1, 0), entry(1, 1)]]
+}
+
+/// Returns `(0, 1; 1, -q) * m`, i.e. `m` followed by a Euclidean step with quotient `q`.
+fn step(field: &Field, quotient: &[u64], m: Matrix) -> Matrix {
+    let [first, second] = m;
+    let next = [
+        add(&first[0], &mul(field, quotient, &second[0])),
+        add(&first[1], &mul(field, quotient, &second[1])),
+    ];
+
+    [second, next]
+}
+
+/// Returns the matrix that maps `(a, b)`, where `deg a > deg b`, to the consecutive remainders
+/// of the Euclidean algorithm with degrees at least `ceil(deg a / 2)` and below it.
+///
+/// Quotients of the first half of the algorithm only depend on the high halves of `a` and `b`, so
+/// it recurses on them twice, following the classic half-GCD algorithm.
+fn half_gcd(field: &Field, a: &[u64], b: &[u64]) -> Matrix {
+    // Degree of a polynomial is below `half` iff its length is at most `half`
+    let half = a.len() / 2;
+    if b.len() <= half {
+        return identity();
+    }
+
+    if a.len() < HALF_GCD_THRESHOLD {
+        let mut matrix = identity();
+        let (mut a, mut b) = (a.to_vec(), b.to_vec());
+        while b.len() > half {
+            let (quotient, remainder) = div_rem(field, &a, &b);
+            matrix = step(field, &quotient, matrix);
+            a = b;
+            b = remainder;
+        }
+        return matrix;
+    }
+
+    let matrix = half_gcd(field, &a[half..], &b[half..]);
+    let [c, d] = apply(field, &matrix, a, b);
+    if d.len() <= half {
+        return matrix;
+    }
+
+    let (quotient, remainder) = div_rem(field, &c, &d);
+    let matrix = step(field, &quotient, matrix);
+    if remainder.len() <= half {
+        return matrix;
+    }
+
+    let shift = 2 * half - (d.len() - 1);
+    let next = half_gcd(field, &d[shift..], remainder.get(shift..).unwrap_or(&[]));
+    mat_mul(field, &next, &matrix)
+}
+
+/// Returns the greatest common divisor of a nonzero polynomial `a` and `b`, up to a factor.
+fn gcd(field: &Field, mut a: Vec<u64>, mut b: Vec<u64>) -> Vec<u64> {
+    trim(&mut b);
+    if b.len() >= a.len() {
+        let (_, remainder) = div_rem(field, &b, &a);
+        b = remainder;
+    }
+
+    while !b.is_empty() {
+        let [c, d] = apply(field, &half_gcd(field, &a, &b), &a, &b);
+        if d.is_empty() {
+            return c;
+        }
+
+        let (_, remainder) = div_rem(field, &c, &d);
+        a = d;
+        b = remainder;
+    }
+
+    a
+}
@@ -0,0 +1,147 @@
+use core::fmt::{Display, Formatter};
+
+/// Error that originates from `libminisketch` or from misuse of the sketch API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MinisketchError {
+    /// The combination of element size, implementation and capacity is not available.
+    UnsupportedParameters {
+        /// Requested element size in bits.
+        bits: u32,
+        /// Requested implementation number.
+        implementation: u32,
+        /// Requested capacity.
+        capacity: usize,
+    },
+
+    /// Two sketches can't be merged because they differ in their element size or implementation.
+    ///
+    /// Both fields hold `(bits, implementation)` of the corresponding sketch.
+    IncompatibleMerge {
+        /// `(bits, implementation)` of the sketch being merged into.
+        ours: (u32, u32),
+        /// `(bits, implementation)` of the sketch being merged from.
+        theirs: (u32, u32),
+    },
+
+    /// Sketch couldn't be decoded because the number of elements in it exceeds its capacity.
+    ///
+    /// `libminisketch` can't tell this apart from a corrupted sketch, which is equally undecodable.
+    DecodeFailed {
+        /// Capacity of the sketch that failed to decode.
+        capacity: usize,
+    },
+
+    /// Sketch couldn't be decoded, with a lower bound on the number of elements in it.
+    ///
+    /// This is what `decode_detailed` reports in place of `DecodeFailed`. The bound only holds if
+    /// the sketch was built from a set, it's meaningless for a corrupted one. It's rarely much
+    /// above the capacity, since the locator can't be longer than the number of power sums allows.
+    DifferenceExceedsCapacity {
+        /// Capacity of the sketch that failed to decode.
+        capacity: usize,
+        /// Degree of the locator polynomial recovered from the sketch, which is at most twice
+        /// the capacity.
+        locator_degree: usize,
+        /// Lower bound on the number of elements in the sketch, i.e. the smallest capacity that
+        /// might decode it. It's the larger of `locator_degree` and `capacity + 1`.
+        min_difference: usize,
+    },
+
+    /// Sketch holds more elements than the provided output buffer can fit.
+    DifferenceExceedsBuffer {
+        /// Size of the output buffer in elements.
+        buffer: usize,
+        /// Number of elements in the sketch.
+        difference: usize,
+    },
+
+    /// Element is zero or doesn't fit into the element size of a sketch.
+    ElementOutOfRange {
+        /// The rejected element.
+        element: u64,
+        /// Element size of the sketch in bits.
+        bits: u32,
+    },
+
+    /// `libminisketch` failed to allocate memory for a sketch.
+    AllocationFailed,
+
+    /// Requested capacity is larger than the capacity of a sketch.
+    CapacityExceeded {
+        /// Requested capacity.
+        requested: usize,
+        /// Capacity of the sketch.
+        capacity: usize,
+    },
+
+    /// Provided buffer has the wrong size for the serialized representation of a sketch.
+    BufferSize {
+        /// Size in bytes that was expected.
+        expected: usize,
+        /// Size in bytes of the buffer that was provided.
+        actual: usize,
+    },
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MinisketchError {}
+
+impl Display for MinisketchError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), core::fmt::Error> {
+        match self {
+            MinisketchError::UnsupportedParameters {
+                bits,
+                implementation,
+                capacity,
+            } => write!(
+                f,
+                "Unsupported minisketch parameters: bits = {}, implementation = {}, capacity = {}",
+                bits, implementation, capacity
+            ),
+            MinisketchError::IncompatibleMerge { ours, theirs } => write!(
+                f,
+                "Can't merge sketch with bits = {}, implementation = {} into sketch with bits = {}, implementation = {}",
+                theirs.0, theirs.1, ours.0, ours.1
+            ),
+            MinisketchError::DecodeFailed { capacity } => write!(
+                f,
+                "Sketch decoding failed, difference exceeds capacity of {}",
+                capacity
+            ),
+            MinisketchError::DifferenceExceedsCapacity {
+                capacity,
+                min_difference,
+                ..
+            } => write!(
+                f,
+                "Sketch decoding failed, difference of at least {} elements exceeds capacity of {}",
+                min_difference, capacity
+            ),
+            MinisketchError::DifferenceExceedsBuffer { buffer, difference } => write!(
+                f,
+                "Difference of {} elements exceeds output buffer of {} elements",
+                difference, buffer
+            ),
+            MinisketchError::ElementOutOfRange { element, bits } => write!(
+                f,
+                "Element {} is not a nonzero value of {} bits",
+                element, bits
+            ),
+            MinisketchError::AllocationFailed => write!(f, "Failed to allocate a sketch"),
+            MinisketchError::CapacityExceeded {
+                requested,
+                capacity,
+            } => write!(
+                f,
+                "Requested capacity of {} exceeds sketch capacity of {}",
+                requested, capacity
+            ),
+            MinisketchError::BufferSize { expected, actual } => write!(
+                f,
+                "Invalid buffer size: expected {} bytes, got {}",
+                expected, actual
+            ),
+        }
+    }
+}
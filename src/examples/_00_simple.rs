@@ -53,30 +53,23 @@
 //! 
 //! fn create_sketch_alice() -> Result<Minisketch, MinisketchError> {
 //!     let set = 3_000..3_010;
-//!     println!(
-//!         "Alice's set: {:?}",
-//!         set.clone().into_iter().collect::<Vec<_>>()
-//!     );
+//!     println!("Alice's set: {:?}", set.clone().collect::<Vec<_>>());
 //! 
-//!     Ok(create_sketch(set)?)
+//!     create_sketch(set)
 //! }
 //! 
 //! fn create_sketch_bob() -> Result<Minisketch, MinisketchError> {
 //!     let set = 3_002..3_012;
-//!     println!(
-//!         "Bob's set: {:?}",
-//!         set.clone().into_iter().collect::<Vec<_>>()
-//!     );
+//!     println!("Bob's set: {:?}", set.clone().collect::<Vec<_>>());
 //! 
-//!     Ok(create_sketch(set)?)
+//!     create_sketch(set)
 //! }
 //! 
 //! fn reconcile_with_bob(msg_alice: &[u8]) -> Result<(), MinisketchError> {
 //!     let mut sketch_bob = create_sketch_bob()?;
 //! 
 //!     // Restore Alice's sketch (not set!) from serialized message
-//!     let mut sketch_alice = Minisketch::try_new(12, 0, 4)?;
-//!     sketch_alice.deserialize(&msg_alice);
+//!     let sketch_alice = Minisketch::from_bytes(12, 0, 4, msg_alice)?;
 //! 
 //!     // Reconcile sets by merging sketches
 //!     sketch_bob.merge(&sketch_alice)?;
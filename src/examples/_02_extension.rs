@@ -0,0 +1,103 @@
+//! Example of set reconciliation that asks for sketch extensions when the number of differences
+//! between two sets exceeds an estimate and decoding fails.
+//!
+//! Instead of starting over with a larger sketch, Alice asks Bob for the next blocks of syndromes
+//! of the very same sketch. Together with the syndromes received before, they form a sketch of a
+//! larger capacity. Both Alice and Bob compute their sketches only once. Failed decoding tells a
+//! lower bound on the number of differences, so Alice skips the blocks that can't be enough.
+//!
+//! ```notrust
+//! +-------+                            +-------+
+//! | Alice |                            |  Bob  |
+//! +-------+                            +-------+
+//!     |                                   |
+//!     | Sketch of a set, capacity 16      | Sketch of a set, capacity 16
+//!     |-----------------------------      |-----------------------------
+//!     |                            |      |                            |
+//!     |<----------------------------      |<----------------------------
+//!     |                                   |
+//!     |        Syndromes 0..4 (initial)   |
+//!     |<----------------------------------|
+//!     |                                   |
+//!     | reconcile()                       |
+//!     |------------                       |
+//!     |           | failed!               |
+//!     |<-----------                       |
+//!     |                                   |
+//!     | Ask for extension                 |
+//!     |---------------------------------->|
+//!     |                                   |
+//!     |    Syndromes 4..8, or up to bound |
+//!     |<----------------------------------|
+//!     |                                   |
+//!     | ... until reconcile() succeeds    |
+//!     |                                   |
+//! ```
+//!
+//! ```
+//! use minisketch_rs::{ExtendableSketch, Minisketch, MinisketchError, SketchParams};
+//! 
+//! /// Number of syndromes in the initial message and in every extension
+//! const BLOCK: usize = 4;
+//! 
+//! /// Maximum number of syndromes Alice and Bob are willing to exchange
+//! const MAX_CAPACITY: usize = 16;
+//! 
+//! /// Maps transaction numbers to 32-bit short IDs that look random
+//! fn short_ids(range: impl Iterator<Item = u32>) -> impl Iterator<Item = u64> {
+//!     range.map(|i| u64::from(i.wrapping_mul(0x9e37_79b9)))
+//! }
+//! 
+//! fn create_sketch(range: impl Iterator<Item = u32>) -> Result<ExtendableSketch, MinisketchError> {
+//!     let params = SketchParams::new(32, 0, MAX_CAPACITY)?;
+//!     let sketch = Minisketch::from_iter_with(params, short_ids(range))?;
+//! 
+//!     Ok(ExtendableSketch::new(sketch))
+//! }
+//! 
+//! pub fn main() -> Result<(), MinisketchError> {
+//!     // There are 10 differences, more than the initial message can decode
+//!     let mut alice = create_sketch(1..=20)?;
+//!     let bob = create_sketch(5..=26)?;
+//! 
+//!     println!("Bob sends syndromes 0..{}", BLOCK);
+//!     alice.receive(BLOCK, &bob.initial_message(BLOCK)?)?;
+//! 
+//!     let mut differences = loop {
+//!         match alice.reconcile_detailed() {
+//!             Ok(differences) => break differences,
+//!             Err(MinisketchError::DifferenceExceedsCapacity {
+//!                 capacity,
+//!                 min_difference,
+//!                 ..
+//!             }) if capacity < MAX_CAPACITY => {
+//!                 println!(
+//!                     "Decoding with {} syndromes failed, there are at least {} differences",
+//!                     capacity, min_difference
+//!                 );
+//! 
+//!                 // Ask for whole blocks, enough to cover the lower bound on the difference
+//!                 let from = alice.received_capacity();
+//!                 let to = (min_difference.div_ceil(BLOCK) * BLOCK).min(MAX_CAPACITY);
+//!                 println!("Bob sends syndromes {}..{}", from, to);
+//!                 alice.receive(to - from, &bob.extension_message(from, to - from)?)?;
+//!             }
+//!             Err(e) => return Err(e),
+//!         }
+//!     };
+//! 
+//!     println!(
+//!         "Success with {} syndromes! Differences: {}",
+//!         alice.received_capacity(),
+//!         differences.len()
+//!     );
+//! 
+//!     differences.sort();
+//!     let mut expected = short_ids((1..=4).chain(21..=26)).collect::<Vec<_>>();
+//!     expected.sort();
+//!     assert_eq!(differences, expected);
+//! 
+//!     Ok(())
+//! }
+//! ```
+// Auto-generated. Do not modify.
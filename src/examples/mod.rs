@@ -2,3 +2,4 @@
 // Auto-generated. Do not modify.
 pub mod _00_simple;
 pub mod _01_bisect;
+pub mod _02_extension;
@@ -0,0 +1,271 @@
+use crate::{Minisketch, MinisketchError};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Sketch of a local set that is sent to a peer and reconciled with the peer's sketch
+/// incrementally, block of syndromes by block.
+///
+/// A sketch of capacity `c` holds the sketches of all lower capacities as a prefix. So instead
+/// of starting over when decoding fails, a peer can be asked for the next block of syndromes,
+/// which together with the ones already received form a sketch of a larger capacity. This is the
+/// sketch extension used by [Erlay].
+///
+/// Both sides create an `ExtendableSketch` from a [`Minisketch`] of their set once, with a capacity
+/// that covers all extensions they are willing to send or receive.
+///
+/// # Examples
+///
+/// ```rust
+/// use minisketch_rs::{ExtendableSketch, Minisketch, SketchParams};
+///
+/// let params = SketchParams::new(32, 0, 8)?;
+/// let alice = Minisketch::from_iter_with(params, vec![3, 10, 42, 1000, 12345, 777777])?;
+/// let bob = Minisketch::from_iter_with(params, vec![3, 10])?;
+/// let mut alice = ExtendableSketch::new(alice);
+/// let bob = ExtendableSketch::new(bob);
+///
+/// // Bob sends a sketch for 3 differences, but there are 4 of them
+/// alice.receive(3, &bob.initial_message(3)?)?;
+/// assert!(alice.reconcile().is_err());
+///
+/// // Alice asks for 2 more syndromes, and decoding succeeds with 5 of them
+/// alice.receive(2, &bob.extension_message(3, 2)?)?;
+/// assert_eq!(alice.received_capacity(), 5);
+///
+/// let mut differences = alice.reconcile()?;
+/// differences.sort();
+/// assert_eq!(differences, vec![42, 1000, 12345, 777777]);
+/// # Ok::<(), minisketch_rs::MinisketchError>(())
+/// ```
+///
+/// [Erlay]: https://arxiv.org/abs/1905.10518
+/// [`Minisketch`]: struct.Minisketch.html
+#[derive(Debug)]
+pub struct ExtendableSketch {
+    local: Minisketch,
+    received: Vec<u8>,
+    received_capacity: usize,
+}
+
+impl ExtendableSketch {
+    /// Creates an extendable sketch from a sketch of the local set.
+    ///
+    /// Capacity of `local` limits both the messages that can be sent and the number of syndromes
+    /// that can be received.
+    pub fn new(local: Minisketch) -> Self {
+        ExtendableSketch {
+            local,
+            received: Vec::new(),
+            received_capacity: 0,
+        }
+    }
+
+    /// Returns capacity of the local sketch, i.e. the maximum capacity it can be extended to.
+    pub fn capacity(&self) -> usize {
+        self.local.capacity()
+    }
+
+    /// Returns the number of syndromes received from a peer so far.
+    pub fn received_capacity(&self) -> usize {
+        self.received_capacity
+    }
+
+    /// Returns the sketch of the local set.
+    pub fn as_minisketch(&self) -> &Minisketch {
+        &self.local
+    }
+
+    /// Returns the sketch of the local set, discarding syndromes received from a peer.
+    pub fn into_minisketch(self) -> Minisketch {
+        self.local
+    }
+
+    /// Serializes the first `capacity` syndromes of the local sketch.
+    ///
+    /// This is the same as [`Minisketch::serialize_prefix`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::CapacityExceeded)` if `capacity` is larger than the capacity
+    /// of the local sketch.
+    ///
+    /// [`Minisketch::serialize_prefix`]: struct.Minisketch.html#method.serialize_prefix
+    pub fn initial_message(&self, capacity: usize) -> Result<Vec<u8>, MinisketchError> {
+        self.extension_message(0, capacity)
+    }
+
+    /// Serializes `count` syndromes of the local sketch that follow the first `from` ones.
+    ///
+    /// Syndromes are packed the same way as in a serialized sketch, so the message is
+    /// [`Minisketch::serialized_size_for`]`(bits, count)` bytes long.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::CapacityExceeded)` if `from + count` is larger than the
+    /// capacity of the local sketch.
+    ///
+    /// [`Minisketch::serialized_size_for`]: struct.Minisketch.html#method.serialized_size_for
+    pub fn extension_message(&self, from: usize, count: usize) -> Result<Vec<u8>, MinisketchError> {
+        let to = from.saturating_add(count);
+        if to > self.capacity() {
+            return Err(MinisketchError::CapacityExceeded {
+                requested: to,
+                capacity: self.capacity(),
+            });
+        }
+
+        let bits = self.local.bits() as usize;
+        let mut full = vec![0u8; self.local.serialized_size()];
+        self.local.serialize(&mut full)?;
+
+        let mut message = vec![0u8; Minisketch::serialized_size_for(bits as u32, count)];
+        copy_bits(&full, from * bits, &mut message, 0, count * bits);
+
+        Ok(message)
+    }
+
+    /// Appends `count` syndromes received from a peer in `message`, produced by
+    /// [`initial_message`] or [`extension_message`] on the peer's side.
+    ///
+    /// Syndromes are appended to the ones received before, so the first message should start
+    /// from syndrome 0 and every next one from [`received_capacity`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::CapacityExceeded)` if the total number of received syndromes
+    /// would exceed the capacity of the local sketch, and `Err(MinisketchError::BufferSize)` if
+    /// `message` has a wrong size for `count` syndromes. In both cases nothing is received.
+    ///
+    /// [`initial_message`]: struct.ExtendableSketch.html#method.initial_message
+    /// [`extension_message`]: struct.ExtendableSketch.html#method.extension_message
+    /// [`received_capacity`]: struct.ExtendableSketch.html#method.received_capacity
+    pub fn receive(&mut self, count: usize, message: &[u8]) -> Result<(), MinisketchError> {
+        let to = self.received_capacity.saturating_add(count);
+        if to > self.capacity() {
+            return Err(MinisketchError::CapacityExceeded {
+                requested: to,
+                capacity: self.capacity(),
+            });
+        }
+
+        let bits = self.local.bits();
+        let size = Minisketch::serialized_size_for(bits, count);
+        if message.len() != size {
+            return Err(MinisketchError::BufferSize {
+                expected: size,
+                actual: message.len(),
+            });
+        }
+
+        let bits = bits as usize;
+        self.received
+            .resize(Minisketch::serialized_size_for(bits as u32, to), 0);
+        copy_bits(
+            message,
+            0,
+            &mut self.received,
+            self.received_capacity * bits,
+            count * bits,
+        );
+        self.received_capacity = to;
+
+        Ok(())
+    }
+
+    /// Discards syndromes received from a peer, so that reconciliation can start over.
+    pub fn clear_received(&mut self) {
+        self.received.clear();
+        self.received_capacity = 0;
+    }
+
+    /// Decodes the difference between the local set and the peer's set from all syndromes
+    /// received so far.
+    ///
+    /// The local sketch is neither modified nor recomputed: a fresh sketch is built from the
+    /// received syndromes and the matching prefix of the local one is merged into it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::DecodeFailed)` if the difference exceeds the number of
+    /// received syndromes, in which case the peer can be asked for an extension. Returns
+    /// `Err(MinisketchError::UnsupportedParameters)` if nothing was received yet.
+    pub fn reconcile(&self) -> Result<Vec<u64>, MinisketchError> {
+        self.difference_sketch()?.decode_to_vec()
+    }
+
+    /// Decodes the difference the same way as [`reconcile`], but on failure reports a lower bound
+    /// on its size, so the peer can be asked for enough syndromes in a single extension.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::DifferenceExceedsCapacity)` if the difference exceeds the
+    /// number of received syndromes, see [`Minisketch::decode_detailed`]. Returns
+    /// `Err(MinisketchError::UnsupportedParameters)` if nothing was received yet.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::{ExtendableSketch, Minisketch, MinisketchError, SketchParams};
+    ///
+    /// let ids = |count: u64| (1..=count).map(|i| i * 0x0123_4567);
+    /// let params = SketchParams::new(32, 0, 16)?;
+    /// let mut alice = ExtendableSketch::new(Minisketch::from_iter_with(params, ids(10))?);
+    /// let bob = ExtendableSketch::new(Minisketch::from_iter_with(params, ids(2))?);
+    /// alice.receive(4, &bob.initial_message(4)?)?;
+    ///
+    /// let differences = loop {
+    ///     match alice.reconcile_detailed() {
+    ///         Ok(differences) => break differences,
+    ///         Err(MinisketchError::DifferenceExceedsCapacity { min_difference, .. }) => {
+    ///             // Skip the capacities that can't decode the difference anyway
+    ///             let from = alice.received_capacity();
+    ///             let count = min_difference.min(alice.capacity()) - from;
+    ///             alice.receive(count, &bob.extension_message(from, count)?)?;
+    ///         }
+    ///         Err(e) => return Err(e),
+    ///     }
+    /// };
+    /// assert_eq!(differences.len(), 8);
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`reconcile`]: struct.ExtendableSketch.html#method.reconcile
+    /// [`Minisketch::decode_detailed`]: struct.Minisketch.html#method.decode_detailed
+    pub fn reconcile_detailed(&self) -> Result<Vec<u64>, MinisketchError> {
+        let remote = self.difference_sketch()?;
+        let mut differences = vec![0u64; remote.capacity()];
+        let num_differences = remote.decode_detailed(&mut differences)?;
+        differences.truncate(num_differences);
+
+        Ok(differences)
+    }
+
+    /// Builds a sketch of the difference from the received syndromes and the matching prefix of
+    /// the local sketch.
+    fn difference_sketch(&self) -> Result<Minisketch, MinisketchError> {
+        let mut remote = Minisketch::from_bytes(
+            self.local.bits(),
+            self.local.implementation(),
+            self.received_capacity,
+            &self.received,
+        )?;
+        let _ = remote.merge(&self.local)?;
+
+        Ok(remote)
+    }
+}
+
+/// Copies `len` bits from `src` starting at bit `src_offset` into `dst` starting at bit
+/// `dst_offset`, in the LSB-first order of serialized sketches.
+fn copy_bits(src: &[u8], src_offset: usize, dst: &mut [u8], dst_offset: usize, len: usize) {
+    for i in 0..len {
+        let (src_bit, dst_bit) = (src_offset + i, dst_offset + i);
+        let mask = 1u8 << (dst_bit % 8);
+
+        if (src[src_bit / 8] >> (src_bit % 8)) & 1 != 0 {
+            dst[dst_bit / 8] |= mask;
+        } else {
+            dst[dst_bit / 8] &= !mask;
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Capacity planning math, ported from upstream `false_positives.h`.
+//!
+//! A sketch of capacity `c` with `b`-bit elements that holds more than `c` elements is
+//! undecodable, but its `c` syndromes may still look like those of some other set of at most
+//! `c` elements. The chance of that is the number of such sets over the number of possible
+//! sketches, `2^(b * c)`. Each extra syndrome beyond the expected number of elements adds `b`
+//! more bits of protection.
+//!
+//! Everything is computed in integers, so peers on different platforms agree on capacities.
+
+/// Values of `floor(106 * log2(1 + i / 32))` for `i` in `0..32`.
+const LOG2_FRACTIONS: [u32; 32] = [
+    0, 4, 9, 13, 18, 22, 26, 30, 34, 37, 41, 45, 48, 52, 55, 58, 62, 65, 68, 71, 74, 77, 80, 82,
+    85, 88, 90, 93, 96, 98, 101, 103,
+];
+
+/// Returns `floor(log2(x!))`, exactly up to `x = 57` and an underestimate above that.
+fn log2_factorial(x: u32) -> u64 {
+    if x == 0 {
+        return 0;
+    }
+
+    let bits = 32 - x.leading_zeros();
+    // Underestimate of floor(106 * log2(x)): floor(log2(x)) is bits - 1, and the 5 bits below
+    // the top one of x add precision
+    let l2_106 =
+        u64::from(106 * (bits - 1) + LOG2_FRACTIONS[((x << (32 - bits)) >> 26) as usize & 31]);
+    let x = u64::from(x);
+    // Stirling's approximation, log2(x!) = 1/212 * (2x + 1) * (106 * log2(x)) - x / ln(2)
+    // + log2(2 * pi) / 2 + ..., with 418079 / 88632748 being exactly 1/212,
+    // -127870026 / 88632748 slightly less than -1 / ln(2), and 117504694 / 88632748 less than
+    // log2(2 * pi) / 2. Only x < 3 needs a correction term. The sum stays positive, so terms are
+    // added before subtracting.
+    (418079 * (2 * x + 1) * l2_106 + 117504694 + 88632748 * u64::from(x < 3) - 127870026 * x)
+        / 88632748
+}
+
+// Corrections to `log2_factorial` that make `base_fp_bits` exact for 5 to 8 bits, indexed by
+// capacity - 3. Narrower fields have theirs packed into integers in `base_fp_bits`.
+static ADD5: [u8; 29] = [
+    0, 0, 1, 1, 1, 1, 2, 2, 2, 3, 4, 4, 5, 5, 6, 7, 8, 8, 9, 10, 10, 10, 11, 11, 11, 12, 12, 12, 12,
+];
+static ADD6: [u8; 61] = [
+    0, 0, 1, 0, 0, 0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 4, 4, 4, 5, 6, 6, 6, 7, 8, 8, 10, 10, 11, 12, 12,
+    13, 14, 15, 15, 16, 17, 18, 18, 19, 20, 20, 21, 21, 22, 22, 23, 23, 23, 24, 24, 24, 24, 25, 25,
+    25, 26, 26, 25, 26, 26, 26,
+];
+static ADD7: [u8; 125] = [
+    0, 0, 1, 0, 0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 4, 4, 4, 5, 5, 5, 6, 6, 7, 7,
+    8, 7, 8, 9, 9, 9, 10, 11, 11, 12, 12, 13, 13, 15, 15, 15, 16, 17, 17, 18, 19, 20, 20, 22, 23,
+    22, 24, 25, 26, 26, 28, 28, 30, 29, 32, 32, 34, 33, 35, 34, 37, 36, 38, 37, 40, 39, 41, 41, 43,
+    42, 44, 43, 45, 44, 46, 45, 47, 46, 48, 47, 49, 47, 49, 48, 50, 49, 51, 50, 51, 50, 52, 51, 52,
+    51, 53, 52, 54, 53, 54, 53, 54, 54, 55, 53, 55, 53, 55, 54, 55, 53, 55, 54, 56,
+];
+static ADD8: [u8; 253] = [
+    0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 1, 1, 1, 1, 1, 2, 1, 1, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4,
+    4, 3, 4, 4, 5, 4, 5, 5, 5, 6, 6, 6, 6, 7, 7, 7, 8, 8, 8, 8, 9, 9, 10, 11, 11, 10, 12, 12, 12,
+    13, 14, 14, 15, 14, 16, 16, 17, 16, 18, 16, 19, 18, 20, 18, 21, 20, 22, 22, 24, 23, 25, 24, 26,
+    25, 27, 26, 28, 27, 29, 28, 31, 29, 31, 30, 33, 32, 34, 34, 35, 35, 37, 36, 38, 37, 40, 39, 42,
+    42, 43, 43, 44, 45, 47, 45, 48, 47, 50, 49, 51, 50, 53, 53, 56, 53, 56, 58, 61, 58, 61, 63, 65,
+    61, 64, 66, 68, 65, 68, 70, 72, 68, 70, 72, 75, 71, 74, 76, 78, 75, 77, 79, 81, 77, 80, 82, 84,
+    80, 82, 84, 86, 84, 86, 88, 90, 86, 88, 90, 92, 88, 90, 92, 94, 91, 93, 95, 97, 92, 94, 96, 98,
+    94, 96, 98, 100, 97, 99, 101, 102, 97, 99, 101, 103, 99, 101, 103, 104, 101, 102, 104, 106,
+    102, 104, 106, 107, 103, 105, 107, 108, 104, 106, 108, 109, 105, 107, 108, 110, 107, 109, 111,
+    112, 108, 109, 111, 113, 108, 110, 111, 113, 110, 112, 113, 115, 110, 111, 113, 114, 109, 111,
+    112, 114, 111, 112, 114, 115, 110, 111, 113, 114, 111, 112, 114, 115,
+];
+
+/// Returns the number of bits of false positive protection a sketch of `bits`-bit elements
+/// and capacity `capacity` has when it's decoded into at most `capacity` elements.
+///
+/// Exact for up to 8 bits, and an underestimate for wider fields.
+fn base_fp_bits(bits: u32, capacity: u32) -> u64 {
+    if capacity == 0 {
+        return 0;
+    }
+
+    let mut ret = 0;
+    let mut capacity = capacity;
+    if bits < 32 && capacity >= 1 << bits {
+        // Every syndrome beyond the number of nonzero field elements adds `bits` bits
+        ret = u64::from(bits) * u64::from(capacity - (1 << bits) + 1);
+        capacity = (1 << bits) - 1;
+    }
+    ret += log2_factorial(capacity);
+
+    let index = capacity.saturating_sub(3) as usize;
+    let correction = match bits {
+        _ if capacity <= 2 => 0,
+        2 => 1,
+        3 => (0x2a5 >> (2 * index)) & 3,
+        4 => (0x5b6c8d2248u64 >> (3 * index)) & 7,
+        5 => ADD5[index].into(),
+        6 => ADD6[index].into(),
+        7 => ADD7[index].into(),
+        8 => ADD8[index].into(),
+        _ => 0,
+    };
+
+    ret + correction
+}
+
+/// Computes the capacity needed to decode up to `max_elements` elements with a false
+/// positive probability of at most `1 / 2^fpbits`.
+pub(crate) fn compute_capacity(bits: u32, max_elements: usize, fpbits: u32) -> usize {
+    if bits == 0 {
+        return 0;
+    }
+    if max_elements > u32::MAX as usize {
+        return max_elements;
+    }
+
+    let base = base_fp_bits(bits, max_elements as u32);
+    let fpbits = u64::from(fpbits);
+
+    if base >= fpbits {
+        max_elements
+    } else {
+        let bits = u64::from(bits);
+        max_elements + (fpbits - base).div_ceil(bits) as usize
+    }
+}
+
+/// Computes the maximum number of elements a sketch of given `capacity` can decode while
+/// keeping false positive probability at most `1 / 2^fpbits`.
+pub(crate) fn compute_max_elements(bits: u32, capacity: usize, fpbits: u32) -> usize {
+    if bits == 0 {
+        return 0;
+    }
+    if capacity > u32::MAX as usize {
+        return capacity;
+    }
+
+    // Start with max_elements = capacity, and decrease it until the matching capacity fits.
+    let mut max_elements = capacity;
+    loop {
+        let capacity_for_max_elements = compute_capacity(bits, max_elements, fpbits);
+        if capacity_for_max_elements <= capacity {
+            return max_elements;
+        }
+
+        // Decrementing max_elements by N decrements the capacity by at most N
+        let adjust = capacity_for_max_elements - capacity;
+        if max_elements <= adjust {
+            return 0;
+        }
+        max_elements -= adjust;
+    }
+}
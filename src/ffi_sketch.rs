@@ -0,0 +1,152 @@
+//! Sketches backed by `libminisketch` through `minisketch-sys`.
+
+use minisketch_sys as ffi;
+
+/// Owned `libminisketch` sketch object.
+#[derive(Debug)]
+pub(crate) struct FfiSketch {
+    inner: *mut ffi::minisketch,
+}
+
+// SAFETY: `FfiSketch` exclusively owns the underlying sketch object. `libminisketch` keeps no
+// thread-local or global mutable state: a sketch is a heap object that holds its own syndromes
+// and seed, while field tables are immutable. So a sketch can be used and dropped on any thread.
+unsafe impl Send for FfiSketch {}
+
+// SAFETY: Methods that take `&self` (getters, `serialize`, `decode` and `try_clone`) call
+// functions that take a `const minisketch*` and only read the object: decoding works on a copy of
+// the syndromes, and the seed is only read. Every function that mutates a sketch is called from a
+// method that takes `&mut self`, so shared access never races with a write.
+unsafe impl Sync for FfiSketch {}
+
+impl FfiSketch {
+    /// Returns `true` if support for `bits`-bit elements was selected and compiled in. An
+    /// installed library is asked directly, as the `bits-*` features didn't build it.
+    pub(crate) fn bits_supported(bits: u32) -> bool {
+        if !ffi::INSTALLED_LIB && !field_selected(bits) {
+            return false;
+        }
+
+        let res = unsafe { ffi::minisketch_bits_supported(bits) };
+        res != 0
+    }
+
+    pub(crate) fn implementation_max() -> u32 {
+        unsafe { ffi::minisketch_implementation_max() }
+    }
+
+    /// Creates an empty sketch, or returns `None` if parameters are unsupported.
+    pub(crate) fn new(bits: u32, implementation: u32, capacity: usize) -> Option<Self> {
+        if !Self::bits_supported(bits) {
+            return None;
+        }
+
+        let inner = unsafe { ffi::minisketch_create(bits, implementation, capacity) };
+        Self::from_raw(inner)
+    }
+
+    fn from_raw(inner: *mut ffi::minisketch) -> Option<Self> {
+        if inner.is_null() {
+            None
+        } else {
+            Some(FfiSketch { inner })
+        }
+    }
+
+    pub(crate) fn bits(&self) -> u32 {
+        unsafe { ffi::minisketch_bits(self.inner) }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        unsafe { ffi::minisketch_capacity(self.inner) }
+    }
+
+    pub(crate) fn implementation(&self) -> u32 {
+        unsafe { ffi::minisketch_implementation(self.inner) }
+    }
+
+    pub(crate) fn serialized_size(&self) -> usize {
+        unsafe { ffi::minisketch_serialized_size(self.inner) }
+    }
+
+    pub(crate) fn add(&mut self, element: u64) {
+        unsafe { ffi::minisketch_add_uint64(self.inner, element) }
+    }
+
+    pub(crate) fn add_all(&mut self, elements: &[u64]) {
+        unsafe {
+            ffi::minisketch_rs_add_uint64_batch(self.inner, elements.as_ptr(), elements.len())
+        }
+    }
+
+    pub(crate) fn set_seed(&mut self, seed: u64) {
+        unsafe { ffi::minisketch_set_seed(self.inner, seed) }
+    }
+
+    /// Clones a sketch, or returns `None` if `libminisketch` failed to allocate a copy.
+    pub(crate) fn try_clone(&self) -> Option<Self> {
+        let inner = unsafe { ffi::minisketch_clone(self.inner) };
+        Self::from_raw(inner)
+    }
+
+    /// Merges `other` into this sketch and returns the resulting capacity, or 0 if the sketches
+    /// differ in their element size or implementation.
+    pub(crate) fn merge(&mut self, other: &Self) -> usize {
+        unsafe { ffi::minisketch_merge(self.inner, other.inner) }
+    }
+
+    /// Decodes at most `elements.len()` elements into `elements`, and returns their number, or
+    /// `None` if decoding failed.
+    pub(crate) fn decode(&self, elements: &mut [u64]) -> Option<usize> {
+        let result =
+            unsafe { ffi::minisketch_decode(self.inner, elements.len(), elements.as_mut_ptr()) };
+
+        if result != -1 {
+            Some(result as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Serializes a sketch into `buf`, which must hold at least [`serialized_size`] bytes.
+    ///
+    /// [`serialized_size`]: #method.serialized_size
+    pub(crate) fn serialize(&self, buf: &mut [u8]) {
+        unsafe { ffi::minisketch_serialize(self.inner, buf.as_mut_ptr()) }
+    }
+
+    /// Deserializes a sketch from `buf`, which must hold at least [`serialized_size`] bytes.
+    ///
+    /// [`serialized_size`]: #method.serialized_size
+    pub(crate) fn deserialize(&mut self, buf: &[u8]) {
+        unsafe { ffi::minisketch_deserialize(self.inner, buf.as_ptr()) }
+    }
+}
+
+/// Frees the underlying opaque sketch.
+impl Drop for FfiSketch {
+    fn drop(&mut self) {
+        if !self.inner.is_null() {
+            unsafe {
+                ffi::minisketch_destroy(self.inner);
+            }
+        }
+    }
+}
+
+/// Field sizes from 1 to 8 bytes selected with `bits-1byte` to `bits-8byte` cargo features.
+const SELECTED_FIELD_BYTES: [bool; 8] = [
+    cfg!(feature = "bits-1byte"),
+    cfg!(feature = "bits-2byte"),
+    cfg!(feature = "bits-3byte"),
+    cfg!(feature = "bits-4byte"),
+    cfg!(feature = "bits-5byte"),
+    cfg!(feature = "bits-6byte"),
+    cfg!(feature = "bits-7byte"),
+    cfg!(feature = "bits-8byte"),
+];
+
+/// Returns `true` if the field for `bits`-bit elements is selected with a `bits-*` cargo feature.
+pub(crate) fn field_selected(bits: u32) -> bool {
+    (2..=64).contains(&bits) && SELECTED_FIELD_BYTES[(bits as usize - 1) / 8]
+}
@@ -0,0 +1,462 @@
+//! Arithmetic in GF(2<sup>bits</sup>), the fields that sketches of `bits`-bit elements work in.
+//!
+//! [`Gf2b`] is an element of GF(2<sup>`BITS`</sup>) for `BITS` from 2 to 64. Elements are
+//! polynomials over GF(2) in the standard basis, stored in the low `BITS` bits of a `u64`, and are
+//! reduced modulo the same irreducible polynomials as in `libminisketch`. So a set element, a
+//! field element and a syndrome in a serialized sketch all share the same representation.
+//!
+//! Multiplication uses carry-less multiply instructions on x86 CPUs that support them, which is
+//! detected at runtime, and a portable implementation otherwise. Without the `std` feature, the
+//! instructions are only used if they're enabled at compile time, e.g. with
+//! `-C target-feature=+pclmulqdq`.
+//!
+//! # Examples
+//!
+//! A sketch of capacity `c` holds the odd power sums `s_1, s_3, ..., s_(2c-1)` of its elements.
+//! With 8-bit elements, every byte of a serialized sketch is one of them:
+//!
+//! ```rust
+//! use minisketch_rs::field::Gf2b;
+//! use minisketch_rs::{Minisketch, SketchParams};
+//!
+//! let elements = [3u64, 5, 200];
+//! let sketch = Minisketch::from_iter_with(SketchParams::new(8, 0, 2)?, elements.iter().copied())?;
+//! let mut message = vec![0u8; sketch.serialized_size()];
+//! sketch.serialize(&mut message)?;
+//!
+//! let syndromes = [1, 3]
+//!     .iter()
+//!     .map(|&power| {
+//!         elements
+//!             .iter()
+//!             .map(|&element| Gf2b::<8>::new(element).unwrap().pow(power))
+//!             .sum::<Gf2b<8>>()
+//!     })
+//!     .map(|syndrome| syndrome.value() as u8)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(syndromes, message);
+//! # Ok::<(), minisketch_rs::MinisketchError>(())
+//! ```
+//!
+//! [`Gf2b`]: struct.Gf2b.html
+
+use crate::typed::{Bits, SupportedBits};
+use core::fmt::{Display, Formatter};
+use core::iter::{Product, Sum};
+use core::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// Irreducible polynomials for fields of 2 to 64 bits, without the `x^bits` term.
+///
+/// For each size it's the trinomial `x^bits + x^k + 1` with the lowest `k`. If there is none,
+/// it's the pentanomial with the lowest value, like `x^8 + x^4 + x^3 + x + 1` for 8 bits.
+#[rustfmt::skip]
+const MODULI: [u64; 63] = [
+    // 2 to 8 bits
+    0x3, 0x3, 0x3, 0x5, 0x3, 0x3, 0x1b,
+    // 9 to 16 bits
+    0x3, 0x9, 0x5, 0x9, 0x1b, 0x21, 0x3, 0x2b,
+    // 17 to 24 bits
+    0x9, 0x9, 0x27, 0x9, 0x5, 0x3, 0x21, 0x1b,
+    // 25 to 32 bits
+    0x9, 0x1b, 0x27, 0x3, 0x5, 0x3, 0x9, 0x8d,
+    // 33 to 40 bits
+    0x401, 0x81, 0x5, 0x201, 0x53, 0x63, 0x11, 0x39,
+    // 41 to 48 bits
+    0x9, 0x81, 0x59, 0x21, 0x1b, 0x3, 0x21, 0x2d,
+    // 49 to 56 bits
+    0x201, 0x1d, 0x4b, 0x9, 0x47, 0x201, 0x81, 0x95,
+    // 57 to 64 bits
+    0x11, 0x80001, 0x95, 0x3, 0x27, 0x20000001, 0x3, 0x1b,
+];
+
+/// Element of GF(2<sup>`BITS`</sup>).
+///
+/// Addition and subtraction are both bitwise XOR. Multiplication is carry-less, modulo
+/// `x^BITS + MODULUS`.
+///
+/// # Examples
+///
+/// ```rust
+/// use minisketch_rs::field::Gf2b;
+/// let a = Gf2b::<12>::new(42).unwrap();
+/// let b = Gf2b::<12>::new(3000).unwrap();
+///
+/// assert_eq!(a + b, Gf2b::new(42 ^ 3000).unwrap());
+/// assert_eq!(a * b * b.inverse().unwrap(), a);
+/// assert_eq!(a.pow(1 << 12), a);
+/// assert_eq!(a.square(), a * a);
+///
+/// // Elements must fit into `BITS` bits
+/// assert!(Gf2b::<12>::new(1 << 12).is_none());
+/// ```
+///
+/// Unsupported element sizes don't compile:
+///
+/// ```compile_fail
+/// use minisketch_rs::field::Gf2b;
+/// let a = Gf2b::<65>::new(1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Gf2b<const BITS: u32>(u64)
+where
+    Bits<BITS>: SupportedBits;
+
+impl<const BITS: u32> Gf2b<BITS>
+where
+    Bits<BITS>: SupportedBits,
+{
+    /// The additive identity.
+    pub const ZERO: Self = Gf2b(0);
+
+    /// The multiplicative identity.
+    pub const ONE: Self = Gf2b(1);
+
+    /// The irreducible polynomial of the field without its `x^BITS` term, as in `libminisketch`.
+    pub const MODULUS: u64 = MODULI[BITS as usize - 2];
+
+    const MASK: u64 = u64::MAX >> (64 - BITS);
+
+    /// Returns the element represented by `value`, or `None` if it doesn't fit into `BITS` bits.
+    pub fn new(value: u64) -> Option<Self> {
+        if value & !Self::MASK == 0 {
+            Some(Gf2b(value))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the element represented by the `BITS` least significant bits of `value`, the same
+    /// way as [`Minisketch::add`] truncates elements.
+    ///
+    /// [`Minisketch::add`]: ../struct.Minisketch.html#method.add
+    pub fn from_truncated(value: u64) -> Self {
+        Gf2b(value & Self::MASK)
+    }
+
+    /// Returns the integer that represents the element.
+    pub fn value(self) -> u64 {
+        self.0
+    }
+
+    /// Returns `true` for the zero element.
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the square of the element.
+    pub fn square(self) -> Self {
+        self * self
+    }
+
+    /// Returns the multiplicative inverse of the element, or `None` for zero.
+    pub fn inverse(self) -> Option<Self> {
+        if self.is_zero() {
+            None
+        } else {
+            Some(Gf2b(inv(self.0, BITS, Self::MODULUS)))
+        }
+    }
+
+    /// Raises the element to the power of `exponent`. Zero to the power of zero is one.
+    pub fn pow(self, exponent: u64) -> Self {
+        let mut result = Self::ONE;
+        for i in (0..64 - exponent.leading_zeros()).rev() {
+            result = result.square();
+            if (exponent >> i) & 1 != 0 {
+                result *= self;
+            }
+        }
+
+        result
+    }
+
+    /// Returns the absolute trace of the element, `x + x^2 + x^4 + ... + x^(2^(BITS - 1))`,
+    /// which is either 0 or 1. It's a linear map, and exactly half of elements have trace 1.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::field::Gf2b;
+    /// let ones = (0..1 << 8)
+    ///     .filter(|&value| Gf2b::<8>::new(value).unwrap().trace())
+    ///     .count();
+    /// assert_eq!(ones, 1 << 7);
+    /// ```
+    pub fn trace(self) -> bool {
+        let mut power = self;
+        let mut trace = self;
+        for _ in 1..BITS {
+            power = power.square();
+            trace += power;
+        }
+
+        trace == Self::ONE
+    }
+}
+
+impl<const BITS: u32> From<Gf2b<BITS>> for u64
+where
+    Bits<BITS>: SupportedBits,
+{
+    fn from(element: Gf2b<BITS>) -> u64 {
+        element.0
+    }
+}
+
+impl<const BITS: u32> Display for Gf2b<BITS>
+where
+    Bits<BITS>: SupportedBits,
+{
+    fn fmt(&self, f: &mut Formatter) -> Result<(), core::fmt::Error> {
+        Display::fmt(&self.0, f)
+    }
+}
+
+// Addition and subtraction are the same in characteristic 2, and every element is its own negation
+#[allow(clippy::suspicious_arithmetic_impl)]
+impl<const BITS: u32> Add for Gf2b<BITS>
+where
+    Bits<BITS>: SupportedBits,
+{
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Gf2b(self.0 ^ rhs.0)
+    }
+}
+
+#[allow(clippy::suspicious_arithmetic_impl)]
+impl<const BITS: u32> Sub for Gf2b<BITS>
+where
+    Bits<BITS>: SupportedBits,
+{
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Gf2b(self.0 ^ rhs.0)
+    }
+}
+
+impl<const BITS: u32> Neg for Gf2b<BITS>
+where
+    Bits<BITS>: SupportedBits,
+{
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self
+    }
+}
+
+impl<const BITS: u32> Mul for Gf2b<BITS>
+where
+    Bits<BITS>: SupportedBits,
+{
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Gf2b(reduce(clmul(self.0, rhs.0), BITS, Self::MODULUS))
+    }
+}
+
+impl<const BITS: u32> AddAssign for Gf2b<BITS>
+where
+    Bits<BITS>: SupportedBits,
+{
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<const BITS: u32> SubAssign for Gf2b<BITS>
+where
+    Bits<BITS>: SupportedBits,
+{
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<const BITS: u32> MulAssign for Gf2b<BITS>
+where
+    Bits<BITS>: SupportedBits,
+{
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl<const BITS: u32> Sum for Gf2b<BITS>
+where
+    Bits<BITS>: SupportedBits,
+{
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, Add::add)
+    }
+}
+
+impl<const BITS: u32> Product for Gf2b<BITS>
+where
+    Bits<BITS>: SupportedBits,
+{
+    fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ONE, Mul::mul)
+    }
+}
+
+/// Field of `bits`-bit elements chosen at runtime, for sketches of any element size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Field {
+    bits: u32,
+    modulus: u64,
+    mask: u64,
+}
+
+impl Field {
+    /// Returns the field of `bits`-bit elements, or `None` if `bits` is not from 2 to 64.
+    pub(crate) fn new(bits: u32) -> Option<Self> {
+        if !(2..=64).contains(&bits) {
+            return None;
+        }
+
+        Some(Field {
+            bits,
+            modulus: MODULI[bits as usize - 2],
+            mask: u64::MAX >> (64 - bits),
+        })
+    }
+
+    /// Returns element size in bits.
+    pub(crate) fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Returns the element represented by the `bits` least significant bits of `value`.
+    pub(crate) fn truncate(&self, value: u64) -> u64 {
+        value & self.mask
+    }
+
+    /// Multiplies two elements.
+    pub(crate) fn mul(&self, a: u64, b: u64) -> u64 {
+        reduce(clmul(a, b), self.bits, self.modulus)
+    }
+
+    /// Returns the sum of products of `a[i]` and `b[b.len() - 1 - i]`, which is a coefficient of
+    /// a polynomial product. It's reduced once, so it's cheaper than summing products.
+    pub(crate) fn dot_reversed(&self, a: &[u64], b: &[u64]) -> u64 {
+        reduce(clmul_dot_reversed(a, b), self.bits, self.modulus)
+    }
+
+    /// Squares an element.
+    pub(crate) fn sqr(&self, a: u64) -> u64 {
+        self.mul(a, a)
+    }
+
+    /// Returns the multiplicative inverse of an element, or 0 for 0.
+    pub(crate) fn inv(&self, a: u64) -> u64 {
+        inv(a, self.bits, self.modulus)
+    }
+}
+
+/// Reduces a product of two `bits`-bit elements modulo `x^bits + modulus`.
+fn reduce(mut product: u128, bits: u32, modulus: u64) -> u64 {
+    // x^bits = modulus, so the high part is folded onto the low one until nothing is left of it.
+    // Moduli have low degree, so that takes at most three rounds.
+    let mask = u64::MAX >> (64 - bits);
+    loop {
+        let high = (product >> bits) as u64;
+        if high == 0 {
+            return product as u64;
+        }
+        product = u128::from(product as u64 & mask) ^ clmul(high, modulus);
+    }
+}
+
+/// Returns the multiplicative inverse of a `bits`-bit element, or 0 for 0.
+fn inv(a: u64, bits: u32, modulus: u64) -> u64 {
+    // a^-1 = a^(2^bits - 2) = a^2 * a^4 * ... * a^(2^(bits - 1))
+    let mut power = a;
+    let mut inverse = 1;
+    for _ in 1..bits {
+        power = reduce(clmul(power, power), bits, modulus);
+        inverse = reduce(clmul(inverse, power), bits, modulus);
+    }
+
+    inverse
+}
+
+/// Returns the carry-less product of two polynomials over GF(2).
+pub(crate) fn clmul(a: u64, b: u64) -> u128 {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if has_clmul() {
+            // SAFETY: The required CPU features are detected above
+            return unsafe { clmul_x86(a, b) };
+        }
+    }
+
+    clmul_portable(a, b)
+}
+
+/// Returns the carry-less sum of products of `a[i]` and `b[b.len() - 1 - i]`.
+fn clmul_dot_reversed(a: &[u64], b: &[u64]) -> u128 {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if has_clmul() {
+            // SAFETY: The required CPU features are detected above
+            return unsafe { clmul_dot_reversed_x86(a, b) };
+        }
+    }
+
+    a.iter()
+        .zip(b.iter().rev())
+        .fold(0, |sum, (&a, &b)| sum ^ clmul_portable(a, b))
+}
+
+/// Returns `true` if the CPU supports the `PCLMULQDQ` instruction.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn has_clmul() -> bool {
+    #[cfg(any(feature = "std", test))]
+    {
+        is_x86_feature_detected!("pclmulqdq") && is_x86_feature_detected!("sse2")
+    }
+
+    // Runtime detection needs `std`, so rely on the features of the compilation target
+    #[cfg(not(any(feature = "std", test)))]
+    {
+        cfg!(all(target_feature = "pclmulqdq", target_feature = "sse2"))
+    }
+}
+
+/// Carry-less sum of products with the `PCLMULQDQ` instruction, inlined into the loop.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "pclmulqdq,sse2")]
+unsafe fn clmul_dot_reversed_x86(a: &[u64], b: &[u64]) -> u128 {
+    a.iter()
+        .zip(b.iter().rev())
+        .fold(0, |sum, (&a, &b)| sum ^ clmul_x86(a, b))
+}
+
+/// Carry-less multiplication with the `PCLMULQDQ` instruction.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "pclmulqdq,sse2")]
+unsafe fn clmul_x86(a: u64, b: u64) -> u128 {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::*;
+
+    let product = _mm_clmulepi64_si128(_mm_set_epi64x(0, a as i64), _mm_set_epi64x(0, b as i64), 0);
+    let mut halves = [0u64; 2];
+    _mm_storeu_si128(halves.as_mut_ptr() as *mut __m128i, product);
+
+    u128::from(halves[0]) | (u128::from(halves[1]) << 64)
+}
+
+/// Carry-less multiplication with shifts, for CPUs without a dedicated instruction.
+pub(crate) fn clmul_portable(a: u64, b: u64) -> u128 {
+    let a = u128::from(a);
+    (0..64).fold(0, |product, i| {
+        product ^ ((a << i) & u128::from((b >> i) & 1).wrapping_neg())
+    })
+}
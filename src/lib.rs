@@ -1,3 +1,4 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![deny(missing_debug_implementations)]
 #![deny(missing_docs)]
 #![deny(unused_results)]
@@ -13,54 +14,85 @@
 //!
 //! This library exposes type-safe Rust bindings for all minisketch functions by providing [`Minisketch`] structure.
 //!
+//! When element size and capacity are known in advance, the [`Sketch`] type checks them at compile time.
+//!
+//! With the `pure-rust` feature and without default features, sketches are implemented in Rust
+//! instead, with serializations identical to those of `libminisketch`, so no C++ toolchain is
+//! needed. It supports element sizes from 2 to 64 bits with a single implementation, 0.
+//!
+//! Without the default `std` feature, the crate is `no_std` and only needs `alloc`. Together with
+//! `pure-rust`, [`Minisketch::decode_array`] and [`Sketch::decode_array`] then decode into arrays
+//! without allocating.
+//!
+//! The [field] module exposes arithmetic in the finite fields that sketches work in, for computing
+//! syndromes or other algebra on sketch contents.
+//!
 //! # Examples
 //!
 //! See the [examples] module.
 //!
 //! [examples]: examples/index.html
+//! [field]: field/index.html
 //! [minisketch]: https://github.com/sipa/minisketch
 //! [`Minisketch`]: struct.Minisketch.html
+//! [`Sketch`]: struct.Sketch.html
+//! [`Minisketch::decode_array`]: struct.Minisketch.html#method.decode_array
+//! [`Sketch::decode_array`]: struct.Sketch.html#method.decode_array
 //! [Pieter Wuille]: https://github.com/sipa
 //! [Erlay]: https://arxiv.org/abs/1905.10518
 
-pub mod examples;
-
-use std::error::Error;
-use std::fmt::{Debug, Display, Formatter};
-use std::ops::BitXorAssign;
+extern crate alloc;
 
-/// Error that originates from `libminisketch`, with a message.
-#[derive(Debug)]
-pub struct MinisketchError(String);
+mod calibration;
+mod decoder;
+mod error;
+pub mod examples;
+mod extendable;
+mod false_positives;
+#[cfg(not(feature = "pure-rust"))]
+mod ffi_sketch;
+pub mod field;
+#[cfg(any(feature = "pure-rust", test))]
+mod native;
+mod offset;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod params;
+pub mod typed;
 
-impl MinisketchError {
-    /// Creates an error with a message.
-    pub fn new(msg: &str) -> Self {
-        MinisketchError(msg.to_owned())
-    }
-}
+pub use decoder::Decoder;
+pub use error::MinisketchError;
+pub use extendable::ExtendableSketch;
+pub use offset::OffsetSketch;
+pub use params::{SketchParams, SketchParamsBuilder};
+pub use typed::Sketch;
 
-impl Error for MinisketchError {}
-impl Display for MinisketchError {
-    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
-        write!(f, "MinisketchError({})", self.0)
-    }
-}
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Formatter};
+use core::iter::Sum;
+use core::ops::{BitXor, BitXorAssign};
 
-#[doc(hidden)]
-mod ffi {
-    #![allow(non_upper_case_globals)]
-    #![allow(non_camel_case_types)]
-    #![allow(non_snake_case)]
+#[cfg(not(feature = "pure-rust"))]
+use ffi_sketch::FfiSketch as RawSketch;
+#[cfg(feature = "pure-rust")]
+use native::NativeSketch as RawSketch;
 
-    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+#[cfg(not(any(feature = "pure-rust", feature = "minisketch-sys")))]
+compile_error!(
+    "Either enable the `pure-rust` feature, or keep the default features to use libminisketch"
+);
 
-    unsafe impl Send for minisketch {}
-}
+// Cargo features can't disable a dependency, so libminisketch would still be built and linked
+#[cfg(all(feature = "pure-rust", feature = "minisketch-sys"))]
+compile_error!(
+    "The `pure-rust` feature can't be combined with features that build libminisketch \
+     (`clmul`, `all-bits`, `bits-*`, `system` or `bindgen`), disable default features to use it"
+);
 
 /// Describes decoded sketches and holding underlying opaque type inside.
 pub struct Minisketch {
-    inner: *mut ffi::minisketch,
+    inner: RawSketch,
     bits: u32,
     implementation: u32,
     capacity: usize,
@@ -72,7 +104,7 @@ impl Minisketch {
     /// # Errors
     ///
     /// If the combination of `bits` and `implementation` is unavailable, or if
-    /// `capacity` is 0, an `Err(MinisketchError)` is returned.
+    /// `capacity` is 0, an `Err(MinisketchError::UnsupportedParameters)` is returned.
     ///
     /// # Examples
     ///
@@ -86,24 +118,145 @@ impl Minisketch {
         implementation: u32,
         capacity: usize,
     ) -> Result<Self, MinisketchError> {
-        let inner = unsafe { ffi::minisketch_create(bits, implementation, capacity) };
-
-        if !inner.is_null() {
-            Ok(Minisketch {
+        match RawSketch::new(bits, implementation, capacity) {
+            Some(inner) => Ok(Minisketch {
                 inner,
                 bits,
                 implementation,
                 capacity,
-            })
-        } else {
-            Err(MinisketchError::new("Unsupported minisketch parameters"))
+            }),
+            None => Err(MinisketchError::UnsupportedParameters {
+                bits,
+                implementation,
+                capacity,
+            }),
         }
     }
 
+    /// Tries to create a new empty sketch with a capacity computed from the expected
+    /// maximum number of elements and desired false positive rate.
+    ///
+    /// The capacity of the resulting sketch is [`compute_capacity`]`(bits, max_elements, fpbits)`.
+    ///
+    /// # Errors
+    ///
+    /// If the combination of `bits` and `implementation` is unavailable, an
+    /// `Err(MinisketchError::UnsupportedParameters)` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// // Sketch that decodes up to 10 elements, and wrongly decodes an overfull sketch
+    /// // with probability of at most 1 / 2^32
+    /// let sketch = Minisketch::try_new_fp(32, 0, 10, 32)?;
+    /// assert!(sketch.capacity() > 10);
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`compute_capacity`]: struct.Minisketch.html#method.compute_capacity
+    pub fn try_new_fp(
+        bits: u32,
+        implementation: u32,
+        max_elements: usize,
+        fpbits: u32,
+    ) -> Result<Self, MinisketchError> {
+        let capacity = Self::compute_capacity(bits, max_elements, fpbits);
+        Self::try_new(bits, implementation, capacity)
+    }
+
+    /// Computes the capacity needed to decode up to `max_elements` elements of size of `bits`
+    /// bits with a false positive probability of at most `1 / 2^fpbits`.
+    ///
+    /// Decoding a sketch that holds more elements than it can decode usually fails, but with small
+    /// probability it succeeds and returns wrong elements. Every element of capacity beyond
+    /// `max_elements` adds `bits` bits of protection against that.
+    ///
+    /// Returns 0 if `bits` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let capacity = Minisketch::compute_capacity(32, 10, 32);
+    /// assert!(capacity > 10);
+    /// assert!(Minisketch::compute_max_elements(32, capacity, 32) >= 10);
+    /// ```
+    pub fn compute_capacity(bits: u32, max_elements: usize, fpbits: u32) -> usize {
+        false_positives::compute_capacity(bits, max_elements, fpbits)
+    }
+
+    /// Computes the maximum number of elements of size of `bits` bits that a sketch of
+    /// given `capacity` can decode with a false positive probability of at most `1 / 2^fpbits`.
+    ///
+    /// This is the inverse of [`compute_capacity`]. Returns 0 if `bits` is 0.
+    ///
+    /// [`compute_capacity`]: struct.Minisketch.html#method.compute_capacity
+    pub fn compute_max_elements(bits: u32, capacity: usize, fpbits: u32) -> usize {
+        false_positives::compute_max_elements(bits, capacity, fpbits)
+    }
+
+    /// Returns the size in bytes for serializing a sketch with given element size and capacity,
+    /// without creating one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let sketch = Minisketch::try_new(12, 0, 3)?;
+    /// assert_eq!(Minisketch::serialized_size_for(12, 3), sketch.serialized_size());
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    pub fn serialized_size_for(bits: u32, capacity: usize) -> usize {
+        (bits as usize * capacity).div_ceil(8)
+    }
+
+    /// Tries to create a new sketch with given parameters that holds all elements of `iter`.
+    ///
+    /// Elements are added in batches, see [`add_all`].
+    ///
+    /// # Errors
+    ///
+    /// If the combination of element size and implementation of `params` is unavailable, an
+    /// `Err(MinisketchError::UnsupportedParameters)` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::{Minisketch, SketchParams};
+    /// let params = SketchParams::new(12, 0, 4)?;
+    /// let sketch = Minisketch::from_iter_with(params, 1..=3)?;
+    /// assert_eq!(sketch.decode_sorted()?, vec![1, 2, 3]);
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`add_all`]: struct.Minisketch.html#method.add_all
+    pub fn from_iter_with(
+        params: SketchParams,
+        iter: impl IntoIterator<Item = u64>,
+    ) -> Result<Self, MinisketchError> {
+        let mut sketch = params.create_sketch()?;
+        sketch.extend(iter);
+
+        Ok(sketch)
+    }
+
     /// Determine whether support for elements of size of `bits` bits was compiled in.
+    ///
+    /// Element sizes are compiled in groups of whole bytes selected with `bits-1byte` to
+    /// `bits-8byte` cargo features, all of them by default. An installed `libminisketch`, linked
+    /// with the `system` feature or `MINISKETCH_LIB_DIR`, supports the sizes it was built with.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// # #[cfg(feature = "bits-4byte")]
+    /// assert!(Minisketch::bits_supported(32));
+    /// assert!(!Minisketch::bits_supported(65));
+    /// ```
     pub fn bits_supported(bits: u32) -> bool {
-        let res = unsafe { ffi::minisketch_bits_supported(bits) };
-        res != 0
+        RawSketch::bits_supported(bits)
     }
 
     /// Determine the maximum number of implementations available.
@@ -115,27 +268,57 @@ impl Minisketch {
     /// function call, inclusive. Note that not every combination of implementation
     /// and element size may exist.
     pub fn implementation_max() -> u32 {
-        unsafe { ffi::minisketch_implementation_max() }
+        RawSketch::implementation_max()
     }
 
     /// Returns element size in a sketch in bits.
     pub fn bits(&self) -> u32 {
-        unsafe { ffi::minisketch_bits(self.inner) }
+        self.inner.bits()
     }
 
     /// Returns capacity of a sketch in number of elements.
     pub fn capacity(&self) -> usize {
-        unsafe { ffi::minisketch_capacity(self.inner) }
+        self.inner.capacity()
     }
 
     /// Returns implementation version number.
     pub fn implementation(&self) -> u32 {
-        unsafe { ffi::minisketch_implementation(self.inner) }
+        self.inner.implementation()
     }
 
     /// Returns the size in bytes for serializing a given sketch.
     pub fn serialized_size(&self) -> usize {
-        unsafe { ffi::minisketch_serialized_size(self.inner) }
+        self.inner.serialized_size()
+    }
+
+    /// Returns element size, implementation and capacity of a sketch.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::{Minisketch, SketchParams};
+    /// let sketch = Minisketch::try_new(12, 0, 4)?;
+    /// assert_eq!(sketch.params(), SketchParams::new(12, 0, 4)?);
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    pub fn params(&self) -> SketchParams {
+        SketchParams::from_sketch(self)
+    }
+
+    /// Returns `true` if `other` sketch can be merged into this one,
+    /// i.e. both have the same element size and implementation.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let sketch = Minisketch::try_new(12, 0, 4)?;
+    /// assert!(sketch.is_mergeable_with(&Minisketch::try_new(12, 0, 8)?));
+    /// assert!(!sketch.is_mergeable_with(&Minisketch::try_new(16, 0, 4)?));
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    pub fn is_mergeable_with(&self, other: &Minisketch) -> bool {
+        self.params().is_mergeable_with(&other.params())
     }
 
     /// Adds a `u64` element to a sketch.
@@ -149,6 +332,8 @@ impl Minisketch {
     /// bits), then this function is a no-op. Sketches cannot contain an element with
     /// the value 0.
     ///
+    /// Use [`try_add`] to reject such elements instead of silently losing them.
+    ///
     /// Note that adding the same element a second time removes it again, as sketches have
     /// set semantics, not multiset semantics.
     ///
@@ -160,8 +345,73 @@ impl Minisketch {
     /// sketch.add(42);
     /// # Ok::<(), minisketch_rs::MinisketchError>(())
     /// ```
+    ///
+    /// [`try_add`]: struct.Minisketch.html#method.try_add
     pub fn add(&mut self, element: u64) {
-        unsafe { ffi::minisketch_add_uint64(self.inner, element) }
+        self.inner.add(element)
+    }
+
+    /// Adds a slice of `u64` elements to a sketch.
+    ///
+    /// This is equivalent to calling [`add`] for every element, but crosses the FFI boundary
+    /// only once for the whole slice.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let mut sketch = Minisketch::try_new(12, 0, 4)?;
+    /// sketch.add_all(&[10, 42]);
+    ///
+    /// assert_eq!(sketch.decode_sorted()?, vec![10, 42]);
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`add`]: struct.Minisketch.html#method.add
+    pub fn add_all(&mut self, elements: &[u64]) {
+        self.inner.add_all(elements)
+    }
+
+    /// Adds a `u64` element to a sketch, rejecting elements that can't be represented in it.
+    ///
+    /// Unlike [`add`], this function neither truncates elements that are wider than the element
+    /// size of the sketch, nor silently ignores 0. To put 0 into a sketch, see [`OffsetSketch`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::ElementOutOfRange)` if `element` is 0 or doesn't fit into
+    /// [`bits`] bits. In that case, the sketch is not modified.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let mut sketch = Minisketch::try_new(12, 0, 4)?;
+    /// sketch.try_add(42)?;
+    ///
+    /// assert!(sketch.try_add(0).is_err());
+    /// assert!(sketch.try_add(1 << 12).is_err());
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`add`]: struct.Minisketch.html#method.add
+    /// [`bits`]: struct.Minisketch.html#method.bits
+    /// [`OffsetSketch`]: struct.OffsetSketch.html
+    pub fn try_add(&mut self, element: u64) -> Result<(), MinisketchError> {
+        Self::check_element(self.bits, element)?;
+
+        self.add(element);
+        Ok(())
+    }
+
+    /// Returns `Err(MinisketchError::ElementOutOfRange)` if `element` is 0 or doesn't fit into
+    /// `bits` bits.
+    pub(crate) fn check_element(bits: u32, element: u64) -> Result<(), MinisketchError> {
+        if element == 0 || (bits < 64 && element >> bits != 0) {
+            return Err(MinisketchError::ElementOutOfRange { element, bits });
+        }
+
+        Ok(())
     }
 
     /// Set the seed for randomizing algorithm choices to a fixed value.
@@ -175,6 +425,9 @@ impl Minisketch {
     /// When seed is `std::u64::MAX`, a fixed internal value with predictable behavior is used.
     /// It is only intended for testing.
     ///
+    /// The seed only affects the order of decoded elements. With the `pure-rust` feature it has
+    /// no effect, since root finding there is deterministic.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -184,7 +437,37 @@ impl Minisketch {
     /// # Ok::<(), minisketch_rs::MinisketchError>(())
     /// ```
     pub fn set_seed(&mut self, seed: u64) {
-        unsafe { ffi::minisketch_set_seed(self.inner, seed) }
+        self.inner.set_seed(seed)
+    }
+
+    /// Tries to clone a sketch, including its seed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::AllocationFailed)` if `libminisketch` failed to allocate
+    /// a copy of the sketch.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let mut sketch = Minisketch::try_new(12, 0, 4)?;
+    /// sketch.add(42);
+    ///
+    /// let copy = sketch.try_clone()?;
+    /// assert_eq!(copy.decode_to_vec()?, vec![42]);
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    pub fn try_clone(&self) -> Result<Self, MinisketchError> {
+        match self.inner.try_clone() {
+            Some(inner) => Ok(Minisketch {
+                inner,
+                bits: self.bits,
+                implementation: self.implementation,
+                capacity: self.capacity,
+            }),
+            None => Err(MinisketchError::AllocationFailed),
+        }
     }
 
     /// Merge the elements of another sketch into this sketch.
@@ -205,7 +488,7 @@ impl Minisketch {
     ///
     /// # Errors
     ///
-    /// Returns `Err(MinisketchError)` to indicate that merging has failed
+    /// Returns `Err(MinisketchError::IncompatibleMerge)` to indicate that merging has failed
     /// because the two input sketches differ in their element size or implementation. If `Err` is
     /// returned, `sketch` (and its capacity) have not been modified.
     ///
@@ -233,90 +516,140 @@ impl Minisketch {
     /// # Ok::<(), minisketch_rs::MinisketchError>(())
     /// ```
     pub fn merge(&mut self, other: &Self) -> Result<usize, MinisketchError> {
-        let capacity = unsafe { ffi::minisketch_merge(self.inner, other.inner) };
+        let capacity = self.inner.merge(&other.inner);
 
         if capacity == 0 {
-            Err(MinisketchError::new("Merge is failed"))
+            Err(MinisketchError::IncompatibleMerge {
+                ours: (self.bits(), self.implementation()),
+                theirs: (other.bits(), other.implementation()),
+            })
         } else {
+            self.capacity = capacity;
             Ok(capacity)
         }
     }
 
-    /// Decode a sketch.
-    ///
-    /// `elements` is a mutable reference to a buffer of `u64`, which will be filled with the
-    /// elements in this sketch.
+    /// Reduces capacity of a sketch to `new_capacity`, keeping its elements and seed.
     ///
-    /// Returns `Ok(num. of decoded elements)`
+    /// A sketch of capacity `c` holds the sketches of all lower capacities as a prefix, so a
+    /// truncated sketch is the same as a sketch of capacity `new_capacity` built from the same
+    /// elements. Has no effect if `new_capacity` is not less than the current capacity.
     ///
     /// # Errors
     ///
-    /// Returns `Err(MinisketchError)` if decoding failed for any reason.
+    /// Returns `Err(MinisketchError::UnsupportedParameters)` if `new_capacity` is 0. In that case
+    /// the sketch is not modified.
     ///
     /// # Examples
     ///
     /// ```rust
     /// use minisketch_rs::Minisketch;
-    /// let mut sketch = Minisketch::try_new(12, 0, 2)?;
+    /// let mut sketch = Minisketch::try_new(12, 0, 8)?;
     /// sketch.add(42);
     /// sketch.add(10);
-    /// let mut elements = [0u64; 2];
-    /// sketch.decode(&mut elements)?;
     ///
-    /// // Elements may come in arbitrary order, so check all possible variants
-    /// assert!((elements[0] == 42 || elements[0] == 10) && (elements[1] == 10 || elements[1] == 42));
+    /// sketch.truncate(2)?;
+    /// assert_eq!(sketch.capacity(), 2);
+    /// assert_eq!(sketch.decode_sorted()?, vec![10, 42]);
     /// # Ok::<(), minisketch_rs::MinisketchError>(())
     /// ```
-    pub fn decode(&self, elements: &mut [u64]) -> Result<usize, MinisketchError> {
-        let result =
-            unsafe { ffi::minisketch_decode(self.inner, elements.len(), elements.as_mut_ptr()) };
-
-        if result == -1 {
-            Err(MinisketchError::new("Sketch decoding failed"))
-        } else {
-            Ok(result as usize)
+    pub fn truncate(&mut self, new_capacity: usize) -> Result<(), MinisketchError> {
+        if new_capacity >= self.capacity() {
+            return Ok(());
         }
+
+        // Merging with an empty sketch lowers capacity and leaves syndromes intact
+        let empty = Self::try_new(self.bits(), self.implementation(), new_capacity)?;
+        let _ = self.merge(&empty)?;
+
+        Ok(())
     }
 
-    /// Deserialize a sketch from bytes.
+    /// Merges two sketches into a new one, leaving both intact.
+    ///
+    /// See [`merge`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::IncompatibleMerge)` if the sketches differ in their element
+    /// size or implementation.
     ///
     /// # Examples
     ///
     /// ```rust
     /// use minisketch_rs::Minisketch;
+    /// let mut sketch_a = Minisketch::try_new(12, 0, 4)?;
+    /// sketch_a.add(10);
     ///
-    /// // Create Alice's sketch
-    /// let mut sketch_alice = Minisketch::try_new(12, 0, 2)?;
-    /// sketch_alice.add(42);
-    /// sketch_alice.add(10);
+    /// let sketch_b = Minisketch::try_new(16, 0, 4)?;
+    /// assert!(sketch_a.try_xor(&sketch_b).is_err());
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
     ///
-    /// // Serialize sketch on Alice's side
-    /// let mut message = vec![0u8; sketch_alice.serialized_size()];
-    /// sketch_alice.serialize(&mut message);
+    /// [`merge`]: struct.Minisketch.html#method.merge
+    pub fn try_xor(&self, other: &Self) -> Result<Self, MinisketchError> {
+        let mut sketch = self.clone();
+        let _ = sketch.merge(other)?;
+
+        Ok(sketch)
+    }
+
+    /// Merges all sketches together into a new one.
     ///
-    /// // ... message is sent from Alice to Bob ...
+    /// Returns `Ok(None)` if `sketches` is empty. The capacity of the result is the lowest
+    /// capacity among the sketches.
     ///
-    /// // Deserialize sketch from Alice on Bob's side
-    /// let mut sketch_bob = Minisketch::try_new(12, 0, 2)?;
-    /// sketch_bob.deserialize(&message);
+    /// # Errors
     ///
-    /// // Decode received sketch
-    /// let mut elements = [0u64; 2];
-    /// sketch_bob.decode(&mut elements)?;
-    /// // Elements may come in arbitrary order, so check all possible variants
-    /// assert!((elements[0] == 42 || elements[0] == 10) && (elements[1] == 10 || elements[1] == 42));
+    /// Returns `Err(MinisketchError::IncompatibleMerge)` on the first sketch that differs in its
+    /// element size or implementation from the first one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let mut sketches = vec![];
+    /// for i in 1..=3 {
+    ///     let mut sketch = Minisketch::try_new(12, 0, 4)?;
+    ///     sketch.add(i);
+    ///     sketches.push(sketch);
+    /// }
+    ///
+    /// let merged = Minisketch::try_merge_all(&sketches)?.unwrap();
+    /// assert_eq!(merged.decode_sorted()?, vec![1, 2, 3]);
     /// # Ok::<(), minisketch_rs::MinisketchError>(())
     /// ```
-    pub fn deserialize(&mut self, buf: &[u8]) {
-        unsafe { ffi::minisketch_deserialize(self.inner, buf.as_ptr()) }
+    pub fn try_merge_all<'a>(
+        sketches: impl IntoIterator<Item = &'a Minisketch>,
+    ) -> Result<Option<Self>, MinisketchError> {
+        let mut sketches = sketches.into_iter();
+        let mut merged = match sketches.next() {
+            Some(first) => first.clone(),
+            None => return Ok(None),
+        };
+
+        for sketch in sketches {
+            let _ = merged.merge(sketch)?;
+        }
+
+        Ok(Some(merged))
     }
 
-    /// Serialize a sketch to bytes.
+    /// Decode a sketch.
+    ///
+    /// `elements` is a mutable reference to a buffer of `u64`, which will be filled with the
+    /// elements in this sketch.
+    ///
+    /// Returns `Ok(num. of decoded elements)`
     ///
     /// # Errors
     ///
-    /// Returns `Err(MinisketchError)` if `.len()` of the provided buffer `buf` is less than a size in bytes of
-    /// the serialized representation of the sketch.
+    /// Returns `Err(MinisketchError::DifferenceExceedsBuffer)` if the sketch is decodable, but
+    /// holds more elements than `elements` can fit. Telling this case apart requires a second
+    /// decoding attempt with a buffer of [`capacity`] elements.
+    ///
+    /// Returns `Err(MinisketchError::DecodeFailed)` if decoding failed for any other reason,
+    /// which typically means that the number of elements exceeds the capacity of the sketch.
     ///
     /// # Examples
     ///
@@ -325,63 +658,562 @@ impl Minisketch {
     /// let mut sketch = Minisketch::try_new(12, 0, 2)?;
     /// sketch.add(42);
     /// sketch.add(10);
+    /// let mut elements = [0u64; 2];
+    /// sketch.decode(&mut elements)?;
     ///
-    /// let mut buf = vec![0u8; sketch.serialized_size()];
-    /// sketch.serialize(&mut buf);
+    /// // Elements may come in arbitrary order, so check all possible variants
+    /// assert!((elements[0] == 42 || elements[0] == 10) && (elements[1] == 10 || elements[1] == 42));
     /// # Ok::<(), minisketch_rs::MinisketchError>(())
     /// ```
-    pub fn serialize(&self, buf: &mut [u8]) -> Result<(), MinisketchError> {
-        let size = self.serialized_size();
+    ///
+    /// [`capacity`]: struct.Minisketch.html#method.capacity
+    pub fn decode(&self, elements: &mut [u64]) -> Result<usize, MinisketchError> {
+        self.decode_with(elements, Decoder::Backend)
+    }
+
+    /// Decode a sketch with a chosen [`Decoder`].
+    ///
+    /// Results and errors are the same as for [`decode`], but [`Decoder::Fast`] is much faster
+    /// for sketches of large capacity.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::{Decoder, Minisketch};
+    /// let mut sketch = Minisketch::try_new(12, 0, 2)?;
+    /// sketch.add(42);
+    /// sketch.add(10);
+    /// let mut elements = [0u64; 2];
+    /// sketch.decode_with(&mut elements, Decoder::Fast)?;
+    ///
+    /// // Elements may come in arbitrary order, so check all possible variants
+    /// assert!((elements[0] == 42 || elements[0] == 10) && (elements[1] == 10 || elements[1] == 42));
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`Decoder`]: enum.Decoder.html
+    /// [`Decoder::Fast`]: enum.Decoder.html#variant.Fast
+    /// [`decode`]: struct.Minisketch.html#method.decode
+    pub fn decode_with(
+        &self,
+        elements: &mut [u64],
+        decoder: Decoder,
+    ) -> Result<usize, MinisketchError> {
+        if let Some(num_elements) = self.decode_raw(elements, decoder) {
+            return Ok(num_elements);
+        }
+
+        let capacity = self.capacity();
+        if elements.len() < capacity {
+            // Sketch might still be decodable, just not into the provided buffer
+            let mut all = vec![0u64; capacity];
 
-        if size < buf.len() {
-            return Err(MinisketchError::new("Invalid size of the output buffer"));
+            if let Some(difference) = self.decode_raw(&mut all, decoder) {
+                return Err(MinisketchError::DifferenceExceedsBuffer {
+                    buffer: elements.len(),
+                    difference,
+                });
+            }
         }
 
-        unsafe { ffi::minisketch_serialize(self.inner, buf.as_mut_ptr()) }
-        Ok(())
+        Err(MinisketchError::DecodeFailed { capacity })
     }
-}
 
-/// Custom `Debug` implementation that shows basic information about opaque `minisketch`.
-impl Debug for Minisketch {
-    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
-        write!(
-            f,
-            "Minisketch {{ bits = {}, implementation = {}, capacity = {} }}",
-            self.bits(),
-            self.implementation(),
-            self.capacity(),
-        )
+    /// Decodes at most `elements.len()` elements with `decoder`, and returns their number, or
+    /// `None` if decoding failed.
+    fn decode_raw(&self, elements: &mut [u64], decoder: Decoder) -> Option<usize> {
+        match decoder {
+            Decoder::Backend => self.inner.decode(elements),
+            Decoder::Fast => {
+                decoder::decode(self.bits(), self.capacity(), &self.to_bytes(), elements)
+            }
+        }
+    }
+
+    /// Decode a sketch, and on failure report how far the difference exceeds the capacity.
+    ///
+    /// This is the same as [`decode`], except that decoding failure recovers the locator
+    /// polynomial of the sketch. Its degree is a lower bound on the number of elements in the
+    /// sketch, so reconciliation can be retried with a sufficient capacity right away, instead of
+    /// guessing it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::DifferenceExceedsCapacity)` in place of
+    /// `Err(MinisketchError::DecodeFailed)`, and `Err(MinisketchError::DifferenceExceedsBuffer)`
+    /// as [`decode`] does.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::{Minisketch, MinisketchError, SketchParams};
+    /// let sketch = Minisketch::from_iter_with(SketchParams::new(32, 0, 8)?, 1..=12)?;
+    ///
+    /// let mut elements = [0u64; 8];
+    /// match sketch.decode_detailed(&mut elements) {
+    ///     Err(MinisketchError::DifferenceExceedsCapacity { min_difference, .. }) => {
+    ///         assert!(min_difference > 8 && min_difference <= 12);
+    ///     }
+    ///     _ => unreachable!(),
+    /// }
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`decode`]: struct.Minisketch.html#method.decode
+    pub fn decode_detailed(&self, elements: &mut [u64]) -> Result<usize, MinisketchError> {
+        match self.decode(elements) {
+            Err(MinisketchError::DecodeFailed { capacity }) => {
+                let locator_degree =
+                    decoder::locator_degree(self.bits(), capacity, &self.to_bytes()).unwrap_or(0);
+
+                Err(MinisketchError::DifferenceExceedsCapacity {
+                    capacity,
+                    locator_degree,
+                    min_difference: locator_degree.max(capacity + 1),
+                })
+            }
+            result => result,
+        }
+    }
+
+    /// Serializes the sketch into a newly allocated vector.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut serialized = vec![0u8; self.serialized_size()];
+        self.inner.serialize(&mut serialized);
+
+        serialized
+    }
+
+    /// Decode a sketch into a newly allocated vector.
+    ///
+    /// The vector is sized after the [`capacity`] of the sketch, so the only way for this
+    /// function to fail is when the number of elements exceeds the capacity.
+    ///
+    /// Elements come in arbitrary order, see [`decode_sorted`] for deterministic output.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::DecodeFailed)` if decoding failed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let mut sketch = Minisketch::try_new(12, 0, 4)?;
+    /// sketch.add(42);
+    /// sketch.add(10);
+    ///
+    /// let elements = sketch.decode_to_vec()?;
+    /// assert_eq!(elements.len(), 2);
+    /// assert!(elements.contains(&42) && elements.contains(&10));
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`capacity`]: struct.Minisketch.html#method.capacity
+    /// [`decode_sorted`]: struct.Minisketch.html#method.decode_sorted
+    pub fn decode_to_vec(&self) -> Result<Vec<u64>, MinisketchError> {
+        let mut elements = vec![0u64; self.capacity()];
+        let num_elements = self.decode(&mut elements)?;
+        elements.truncate(num_elements);
+
+        Ok(elements)
+    }
+
+    /// Decode a sketch into a newly allocated vector, sorted in ascending order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::DecodeFailed)` if decoding failed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let mut sketch = Minisketch::try_new(12, 0, 4)?;
+    /// sketch.add(42);
+    /// sketch.add(10);
+    ///
+    /// assert_eq!(sketch.decode_sorted()?, vec![10, 42]);
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    pub fn decode_sorted(&self) -> Result<Vec<u64>, MinisketchError> {
+        let mut elements = self.decode_to_vec()?;
+        elements.sort_unstable();
+
+        Ok(elements)
+    }
+
+    /// Decode a sketch into a fixed-size array.
+    ///
+    /// Returns `Ok((elements, num. of decoded elements))`, only the first `num` elements of the
+    /// array are meaningful, the rest are zeroes.
+    ///
+    /// With the `pure-rust` feature, decoding doesn't allocate, which suits `no_std` targets
+    /// without a heap to spare. Its working memory is a dozen or so arrays of `N` elements on the
+    /// stack, so keep `N` moderate there. Otherwise, this is [`decode`] into an array, and
+    /// `libminisketch` allocates its working memory on the heap.
+    ///
+    /// # Errors
+    ///
+    /// Same as for [`decode`]. With the `pure-rust` feature, if `N` is less than the [`capacity`]
+    /// of the sketch, decoding failure allocates to tell apart `DifferenceExceedsBuffer` from
+    /// `DecodeFailed`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let mut sketch = Minisketch::try_new(12, 0, 4)?;
+    /// sketch.add(42);
+    /// sketch.add(10);
+    ///
+    /// let (elements, num_elements) = sketch.decode_array::<4>()?;
+    /// assert_eq!(num_elements, 2);
+    /// assert!(elements[..num_elements].contains(&42) && elements[..num_elements].contains(&10));
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`decode`]: struct.Minisketch.html#method.decode
+    /// [`capacity`]: struct.Minisketch.html#method.capacity
+    pub fn decode_array<const N: usize>(&self) -> Result<([u64; N], usize), MinisketchError> {
+        let mut elements = [0u64; N];
+
+        #[cfg(feature = "pure-rust")]
+        let num_elements = match self.inner.decode_array(&mut elements) {
+            Some(num_elements) => num_elements,
+            // The difference may fit into the sketch, but not into the array
+            None if N < self.capacity() => self.decode(&mut elements)?,
+            None => {
+                return Err(MinisketchError::DecodeFailed {
+                    capacity: self.capacity(),
+                })
+            }
+        };
+        #[cfg(not(feature = "pure-rust"))]
+        let num_elements = self.decode(&mut elements)?;
+
+        Ok((elements, num_elements))
+    }
+
+    /// Tries to create a sketch from its serialized representation.
+    ///
+    /// This is a shorthand for [`try_new`] followed by [`deserialize`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::UnsupportedParameters)` if the parameters are unsupported, or
+    /// `Err(MinisketchError::BufferSize)` if the length of `buf` differs from the serialized size
+    /// of a sketch with given `bits` and `capacity`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let mut sketch = Minisketch::try_new(12, 0, 2)?;
+    /// sketch.add(42);
+    ///
+    /// let mut buf = vec![0u8; sketch.serialized_size()];
+    /// sketch.serialize(&mut buf)?;
+    ///
+    /// let restored = Minisketch::from_bytes(12, 0, 2, &buf)?;
+    /// assert_eq!(restored.capacity(), 2);
+    ///
+    /// // Truncated messages are rejected
+    /// assert!(Minisketch::from_bytes(12, 0, 2, &buf[1..]).is_err());
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`try_new`]: struct.Minisketch.html#method.try_new
+    /// [`deserialize`]: struct.Minisketch.html#method.deserialize
+    pub fn from_bytes(
+        bits: u32,
+        implementation: u32,
+        capacity: usize,
+        buf: &[u8],
+    ) -> Result<Self, MinisketchError> {
+        let mut sketch = Self::try_new(bits, implementation, capacity)?;
+        sketch.deserialize(buf)?;
+
+        Ok(sketch)
+    }
+
+    /// Deserialize a sketch from bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::BufferSize)` if `.len()` of the provided buffer `buf` is not equal to
+    /// the size in bytes of the serialized representation of the sketch. In that case the sketch is
+    /// not modified.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    ///
+    /// // Create Alice's sketch
+    /// let mut sketch_alice = Minisketch::try_new(12, 0, 2)?;
+    /// sketch_alice.add(42);
+    /// sketch_alice.add(10);
+    ///
+    /// // Serialize sketch on Alice's side
+    /// let mut message = vec![0u8; sketch_alice.serialized_size()];
+    /// sketch_alice.serialize(&mut message)?;
+    ///
+    /// // ... message is sent from Alice to Bob ...
+    ///
+    /// // Deserialize sketch from Alice on Bob's side
+    /// let mut sketch_bob = Minisketch::try_new(12, 0, 2)?;
+    /// sketch_bob.deserialize(&message)?;
+    ///
+    /// // Decode received sketch
+    /// let mut elements = [0u64; 2];
+    /// sketch_bob.decode(&mut elements)?;
+    /// // Elements may come in arbitrary order, so check all possible variants
+    /// assert!((elements[0] == 42 || elements[0] == 10) && (elements[1] == 10 || elements[1] == 42));
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    pub fn deserialize(&mut self, buf: &[u8]) -> Result<(), MinisketchError> {
+        let size = self.serialized_size();
+
+        if buf.len() != size {
+            return Err(MinisketchError::BufferSize {
+                expected: size,
+                actual: buf.len(),
+            });
+        }
+
+        self.inner.deserialize(buf);
+        Ok(())
+    }
+
+    /// Serialize a sketch to bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::BufferSize)` if `.len()` of the provided buffer `buf` is less than a size in bytes of
+    /// the serialized representation of the sketch. If `buf` is larger, only the first
+    /// [`serialized_size`] bytes are written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let mut sketch = Minisketch::try_new(12, 0, 2)?;
+    /// sketch.add(42);
+    /// sketch.add(10);
+    ///
+    /// let mut buf = vec![0u8; sketch.serialized_size()];
+    /// sketch.serialize(&mut buf)?;
+    ///
+    /// // Buffers that are too small are rejected
+    /// let mut small = vec![0u8; sketch.serialized_size() - 1];
+    /// assert!(sketch.serialize(&mut small).is_err());
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`serialized_size`]: struct.Minisketch.html#method.serialized_size
+    pub fn serialize(&self, buf: &mut [u8]) -> Result<(), MinisketchError> {
+        let size = self.serialized_size();
+
+        if buf.len() < size {
+            return Err(MinisketchError::BufferSize {
+                expected: size,
+                actual: buf.len(),
+            });
+        }
+
+        self.inner.serialize(buf);
+        Ok(())
+    }
+
+    /// Serialize a sketch to bytes as if it had the lower `capacity`, see [`truncate`].
+    ///
+    /// The result is a prefix of the full serialization, and can be deserialized into a sketch of
+    /// that `capacity`. Sketch itself is not modified.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::CapacityExceeded)` if `capacity` is larger than the capacity of
+    /// the sketch, and `Err(MinisketchError::BufferSize)` if `.len()` of the provided buffer `buf`
+    /// is less than [`serialized_size_for`]`(bits, capacity)`. If `buf` is larger, only that
+    /// many bytes are written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let mut sketch = Minisketch::try_new(12, 0, 64)?;
+    /// sketch.add(42);
+    /// sketch.add(10);
+    ///
+    /// // Peer only needs to decode a couple of elements
+    /// let mut buf = vec![0u8; Minisketch::serialized_size_for(12, 3)];
+    /// sketch.serialize_prefix(3, &mut buf)?;
+    ///
+    /// let restored = Minisketch::from_bytes(12, 0, 3, &buf)?;
+    /// assert_eq!(restored.decode_sorted()?, vec![10, 42]);
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`truncate`]: struct.Minisketch.html#method.truncate
+    /// [`serialized_size_for`]: struct.Minisketch.html#method.serialized_size_for
+    pub fn serialize_prefix(&self, capacity: usize, buf: &mut [u8]) -> Result<(), MinisketchError> {
+        if capacity > self.capacity() {
+            return Err(MinisketchError::CapacityExceeded {
+                requested: capacity,
+                capacity: self.capacity(),
+            });
+        }
+
+        let size = Self::serialized_size_for(self.bits(), capacity);
+        if buf.len() < size {
+            return Err(MinisketchError::BufferSize {
+                expected: size,
+                actual: buf.len(),
+            });
+        }
+
+        let mut full = vec![0u8; self.serialized_size()];
+        self.serialize(&mut full)?;
+        buf[..size].copy_from_slice(&full[..size]);
+        mask_trailing_bits(&mut buf[..size], self.bits() as usize * capacity);
+
+        Ok(())
+    }
+
+    /// Deserialize a sketch from a prefix of a serialized sketch of the same element size and
+    /// implementation, but a capacity at least as large as that of this sketch.
+    ///
+    /// Only the first [`serialized_size`] bytes of `buf` are read. Bits of the last byte that
+    /// belong to syndromes beyond the capacity of this sketch are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::BufferSize)` if `.len()` of the provided buffer `buf` is less
+    /// than the size in bytes of the serialized representation of the sketch. In that case the
+    /// sketch is not modified.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::Minisketch;
+    /// let mut large = Minisketch::try_new(12, 0, 64)?;
+    /// large.add(42);
+    /// large.add(10);
+    ///
+    /// let mut message = vec![0u8; large.serialized_size()];
+    /// large.serialize(&mut message)?;
+    ///
+    /// let mut small = Minisketch::try_new(12, 0, 3)?;
+    /// small.deserialize_prefix(&message)?;
+    /// assert_eq!(small.decode_sorted()?, vec![10, 42]);
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`serialized_size`]: struct.Minisketch.html#method.serialized_size
+    pub fn deserialize_prefix(&mut self, buf: &[u8]) -> Result<(), MinisketchError> {
+        let size = self.serialized_size();
+
+        if buf.len() < size {
+            return Err(MinisketchError::BufferSize {
+                expected: size,
+                actual: buf.len(),
+            });
+        }
+
+        let mut prefix = buf[..size].to_vec();
+        mask_trailing_bits(&mut prefix, self.bits() as usize * self.capacity());
+        self.deserialize(&prefix)
     }
 }
 
-/// Custom `Drop` implementation that frees an underlying opaque sketch.
-#[doc(hidden)]
-impl Drop for Minisketch {
-    fn drop(&mut self) {
-        unsafe {
-            ffi::minisketch_destroy(self.inner);
+/// Clears bits of `buf` starting from bit `len`, in the LSB-first order of serialized sketches.
+fn mask_trailing_bits(buf: &mut [u8], len: usize) {
+    let used = len % 8;
+    if used > 0 {
+        if let Some(last) = buf.get_mut(len / 8) {
+            *last &= (1u8 << used) - 1;
         }
     }
 }
 
+/// Custom `Debug` implementation that shows basic information about opaque `minisketch`.
+impl Debug for Minisketch {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), core::fmt::Error> {
+        write!(
+            f,
+            "Minisketch {{ bits = {}, implementation = {}, capacity = {} }}",
+            self.bits(),
+            self.implementation(),
+            self.capacity(),
+        )
+    }
+}
+
 /// Custom `Clone` implementation that clones an underlying opaque sketch.
+///
+/// # Panics
+///
+/// Panics if `libminisketch` fails to allocate a copy of the sketch, see [`try_clone`].
+///
+/// [`try_clone`]: struct.Minisketch.html#method.try_clone
 #[doc(hidden)]
 impl Clone for Minisketch {
     fn clone(&self) -> Self {
-        let inner = unsafe { ffi::minisketch_clone(self.inner) };
+        self.try_clone().expect("Failed to clone a sketch")
+    }
+}
+
+/// Number of elements that are buffered before being added to a sketch with [`add_all`].
+///
+/// [`add_all`]: struct.Minisketch.html#method.add_all
+const BATCH_SIZE: usize = 1024;
+
+/// Adds elements of an iterator to a sketch in batches.
+///
+/// # Example
+///
+/// ```rust
+/// use minisketch_rs::Minisketch;
+/// let mut sketch = Minisketch::try_new(12, 0, 4)?;
+/// sketch.extend(vec![10, 42]);
+///
+/// assert_eq!(sketch.decode_sorted()?, vec![10, 42]);
+/// # Ok::<(), minisketch_rs::MinisketchError>(())
+/// ```
+impl Extend<u64> for Minisketch {
+    fn extend<I: IntoIterator<Item = u64>>(&mut self, iter: I) {
+        let mut batch = [0u64; BATCH_SIZE];
+        let mut len = 0;
+
+        for element in iter {
+            batch[len] = element;
+            len += 1;
 
-        Minisketch {
-            inner,
-            bits: self.bits,
-            implementation: self.implementation,
-            capacity: self.capacity,
+            if len == BATCH_SIZE {
+                self.add_all(&batch);
+                len = 0;
+            }
         }
+
+        self.add_all(&batch[..len]);
+    }
+}
+
+/// Adds elements of an iterator to a sketch in batches.
+impl<'a> Extend<&'a u64> for Minisketch {
+    fn extend<I: IntoIterator<Item = &'a u64>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied())
     }
 }
 
 /// Custom `^=` operator implementation on two sketches that performs merging.
 ///
+/// # Panics
+///
+/// In debug builds, panics if the sketches differ in their element size or implementation.
+/// In release builds, `self` is left unmodified in that case. Use [`merge`] to handle the error.
+///
 /// # Example
 ///
 /// ```rust
@@ -395,7 +1227,7 @@ impl Clone for Minisketch {
 /// sketch_b.add(43);
 ///
 /// // Merge two sketches with ^= operator
-/// sketch_a ^= sketch_b;
+/// sketch_a ^= &sketch_b;
 ///
 /// // Extract difference
 /// let mut differences = vec![0u64; 2];
@@ -405,15 +1237,117 @@ impl Clone for Minisketch {
 ///
 /// # Ok::<(), minisketch_rs::MinisketchError>(())
 /// ```
-impl BitXorAssign for Minisketch {
-    fn bitxor_assign(&mut self, rhs: Minisketch) {
-        let _ = self.merge(&rhs);
+///
+/// [`merge`]: struct.Minisketch.html#method.merge
+impl BitXorAssign<&Minisketch> for Minisketch {
+    fn bitxor_assign(&mut self, rhs: &Minisketch) {
+        if let Err(e) = self.merge(rhs) {
+            if cfg!(debug_assertions) {
+                panic!("{}", e);
+            }
+        }
+    }
+}
+
+/// Custom `^=` operator implementation that merges a sketch taken by value.
+///
+/// See the `^=` implementation for `&Minisketch`.
+impl BitXorAssign for Minisketch {
+    fn bitxor_assign(&mut self, rhs: Minisketch) {
+        *self ^= &rhs;
+    }
+}
+
+/// Custom `^` operator implementation that merges two sketches into a new one.
+///
+/// # Panics
+///
+/// In debug builds, panics if the sketches differ in their element size or implementation.
+/// In release builds, a copy of `self` is returned in that case. Use [`try_xor`] to handle the error.
+///
+/// # Example
+///
+/// ```rust
+/// use minisketch_rs::Minisketch;
+/// let mut sketch_a = Minisketch::try_new(12, 0, 4)?;
+/// sketch_a.add(10);
+///
+/// let mut sketch_b = Minisketch::try_new(12, 0, 4)?;
+/// sketch_b.add(42);
+///
+/// let merged = &sketch_a ^ &sketch_b;
+/// assert_eq!(merged.decode_sorted()?, vec![10, 42]);
+/// # Ok::<(), minisketch_rs::MinisketchError>(())
+/// ```
+///
+/// [`try_xor`]: struct.Minisketch.html#method.try_xor
+impl BitXor<&Minisketch> for &Minisketch {
+    type Output = Minisketch;
+
+    fn bitxor(self, rhs: &Minisketch) -> Minisketch {
+        let mut sketch = self.clone();
+        sketch ^= rhs;
+        sketch
+    }
+}
+
+/// Custom `^` operator implementation that merges a sketch into the one taken by value.
+///
+/// See the `^` implementation for `&Minisketch`.
+impl BitXor<&Minisketch> for Minisketch {
+    type Output = Minisketch;
+
+    fn bitxor(mut self, rhs: &Minisketch) -> Minisketch {
+        self ^= rhs;
+        self
+    }
+}
+
+/// Merges all sketches of an iterator together, returns `None` if the iterator is empty.
+///
+/// # Panics
+///
+/// In debug builds, panics if the sketches differ in their element size or implementation.
+/// Use [`try_merge_all`] to handle the error.
+///
+/// # Example
+///
+/// ```rust
+/// use minisketch_rs::Minisketch;
+/// let mut sketches = vec![];
+/// for i in 1..=3 {
+///     let mut sketch = Minisketch::try_new(12, 0, 4)?;
+///     sketch.add(i);
+///     sketches.push(sketch);
+/// }
+///
+/// let merged: Option<Minisketch> = sketches.iter().sum();
+/// assert_eq!(merged.unwrap().decode_sorted()?, vec![1, 2, 3]);
+/// # Ok::<(), minisketch_rs::MinisketchError>(())
+/// ```
+///
+/// [`try_merge_all`]: struct.Minisketch.html#method.try_merge_all
+impl<'a> Sum<&'a Minisketch> for Option<Minisketch> {
+    fn sum<I: Iterator<Item = &'a Minisketch>>(mut iter: I) -> Self {
+        let first = iter.next()?.clone();
+        Some(iter.fold(first, |acc, sketch| acc ^ sketch))
+    }
+}
+
+/// Merges all sketches of an iterator together, returns `None` if the iterator is empty.
+///
+/// See the `Sum` implementation for `&Minisketch`.
+impl Sum<Minisketch> for Option<Minisketch> {
+    fn sum<I: Iterator<Item = Minisketch>>(mut iter: I) -> Self {
+        let first = iter.next()?;
+        Some(iter.fold(first, |acc, sketch| acc ^ &sketch))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::*;
+    use std::convert::TryFrom;
 
     fn validate_elements(elements: &[u64]) {
         // Sort differences since they're come in arbitrary order from minisketch_decode()
@@ -427,9 +1361,10 @@ mod tests {
     }
 
     #[test]
+    #[cfg(not(feature = "pure-rust"))]
     // Implement an example from minisketch's README
     pub fn test_sanity_check() {
-        use ffi::*;
+        use minisketch_sys::*;
         unsafe {
             // Alice's side
             let sketch_a = minisketch_create(12, 0, 4);
@@ -499,7 +1434,7 @@ mod tests {
             let mut sketch_b = sketch_b.clone();
             // Alice's sketch
             let mut sketch_a = Minisketch::try_new(12, 0, 4).unwrap();
-            sketch_a.deserialize(&buf_a); // Load Alice's sketch
+            sketch_a.deserialize(&buf_a).unwrap(); // Load Alice's sketch
 
             // Merge the elements from sketch_a into sketch_b. The result is a sketch_b
             // which contains all elements that occurred in Alice's or Bob's sets, but not
@@ -519,7 +1454,7 @@ mod tests {
 
             // Alice's sketch
             let mut sketch_a = Minisketch::try_new(12, 0, 4).unwrap();
-            sketch_a.deserialize(&buf_a); // Load Alice's sketch
+            sketch_a.deserialize(&buf_a).unwrap(); // Load Alice's sketch
 
             // Merge the elements from sketch_a into sketch_b. The result is a sketch_b
             // which contains all elements that occurred in Alice's or Bob's sets, but not
@@ -533,4 +1468,1339 @@ mod tests {
             validate_elements(&differences[..]);
         }
     }
+
+    #[test]
+    pub fn serialization_buffer_sizes() {
+        let mut sketch = Minisketch::try_new(12, 0, 4).unwrap();
+        sketch.add(42);
+        let size = sketch.serialized_size();
+
+        // Undersized output buffer is rejected, oversized is fine
+        assert!(sketch.serialize(&mut vec![0u8; size - 1]).is_err());
+        let mut buf = vec![0u8; size + 2];
+        sketch.serialize(&mut buf).unwrap();
+        assert_eq!(&buf[size..], &[0, 0]);
+
+        // Input buffer must be of the exact size
+        let mut restored = Minisketch::try_new(12, 0, 4).unwrap();
+        assert!(restored.deserialize(&buf[..size - 1]).is_err());
+        assert!(restored.deserialize(&buf).is_err());
+        restored.deserialize(&buf[..size]).unwrap();
+
+        assert_eq!(
+            Minisketch::from_bytes(12, 0, 4, &buf[..size - 1]).unwrap_err(),
+            MinisketchError::BufferSize {
+                expected: size,
+                actual: size - 1
+            }
+        );
+        let restored = Minisketch::from_bytes(12, 0, 4, &buf[..size]).unwrap();
+
+        let mut elements = [0u64; 4];
+        let num_elements = restored.decode(&mut elements).unwrap();
+        assert_eq!(&elements[..num_elements], &[42]);
+    }
+
+    #[test]
+    pub fn error_variants() {
+        assert_eq!(
+            Minisketch::try_new(12, 0, 0).unwrap_err(),
+            MinisketchError::UnsupportedParameters {
+                bits: 12,
+                implementation: 0,
+                capacity: 0
+            }
+        );
+
+        let mut sketch_a = Minisketch::try_new(12, 0, 2).unwrap();
+        let sketch_b = Minisketch::try_new(16, 0, 2).unwrap();
+        assert_eq!(
+            sketch_a.merge(&sketch_b).unwrap_err(),
+            MinisketchError::IncompatibleMerge {
+                ours: (12, 0),
+                theirs: (16, 0)
+            }
+        );
+
+        for i in 1..=3 {
+            sketch_a.add(i);
+        }
+        let mut elements = [0u64; 2];
+        assert_eq!(
+            sketch_a.decode(&mut elements).unwrap_err(),
+            MinisketchError::DecodeFailed { capacity: 2 }
+        );
+    }
+
+    #[test]
+    pub fn capacity_planning() {
+        // Large fields with enough elements provide enough protection on their own
+        assert_eq!(Minisketch::compute_capacity(64, 30, 16), 30);
+        // log2(10!) is just under 22 bits, so 32 bits need one extra element
+        assert_eq!(Minisketch::compute_capacity(32, 10, 16), 10);
+        assert_eq!(Minisketch::compute_capacity(32, 10, 32), 11);
+        assert_eq!(Minisketch::compute_capacity(32, 10, 64), 12);
+        assert_eq!(Minisketch::compute_capacity(0, 10, 64), 0);
+
+        // Small fields, with max_elements of 1, 2, 3, 4, 8 and 16
+        let known_answers: [(u32, u32, [usize; 6]); 14] = [
+            (2, 16, [9, 10, 10, 10, 10, 16]),
+            (2, 32, [17, 18, 18, 18, 18, 18]),
+            (3, 16, [7, 7, 8, 8, 8, 16]),
+            (3, 32, [12, 13, 13, 13, 13, 16]),
+            (4, 16, [5, 6, 7, 7, 8, 16]),
+            (4, 32, [9, 10, 11, 11, 12, 16]),
+            (5, 16, [5, 5, 6, 7, 8, 16]),
+            (5, 32, [8, 9, 9, 10, 12, 16]),
+            (6, 16, [4, 5, 6, 6, 9, 16]),
+            (6, 32, [7, 8, 8, 9, 11, 16]),
+            (7, 16, [4, 5, 5, 6, 9, 16]),
+            (7, 32, [6, 7, 8, 8, 11, 16]),
+            (8, 16, [3, 4, 5, 6, 9, 16]),
+            (8, 32, [5, 6, 7, 8, 11, 16]),
+        ];
+        for &(bits, fpbits, capacities) in &known_answers {
+            for (&max_elements, &capacity) in [1, 2, 3, 4, 8, 16].iter().zip(&capacities) {
+                assert_eq!(
+                    Minisketch::compute_capacity(bits, max_elements, fpbits),
+                    capacity,
+                    "bits {}, max_elements {}, fpbits {}",
+                    bits,
+                    max_elements,
+                    fpbits
+                );
+            }
+        }
+
+        // Capacities of at least 2^bits, where each extra syndrome adds `bits` bits
+        assert_eq!(Minisketch::compute_capacity(2, 4, 8), 6);
+        assert_eq!(Minisketch::compute_capacity(2, 7, 8), 7);
+        assert_eq!(Minisketch::compute_capacity(3, 11, 8), 11);
+        assert_eq!(Minisketch::compute_capacity(4, 19, 8), 19);
+        assert_eq!(Minisketch::compute_capacity(2, 3, 64), 34);
+        assert_eq!(Minisketch::compute_capacity(4, 15, 64), 20);
+        assert_eq!(Minisketch::compute_capacity(8, 255, 64), 255);
+        assert_eq!(Minisketch::compute_max_elements(2, 6, 8), 6);
+        assert_eq!(Minisketch::compute_max_elements(4, 20, 64), 20);
+
+        for &bits in &[2, 8, 12, 32, 64] {
+            for &fpbits in &[0, 8, 16, 64] {
+                for max_elements in 0..64 {
+                    let capacity = Minisketch::compute_capacity(bits, max_elements, fpbits);
+                    assert!(capacity >= max_elements);
+                    assert!(
+                        Minisketch::compute_max_elements(bits, capacity, fpbits) >= max_elements
+                    );
+                }
+            }
+        }
+
+        let sketch = Minisketch::try_new_fp(32, 0, 10, 32).unwrap();
+        assert_eq!(sketch.capacity(), 11);
+        assert_eq!(
+            Minisketch::serialized_size_for(32, 11),
+            sketch.serialized_size()
+        );
+        assert_eq!(Minisketch::serialized_size_for(12, 3), 5);
+    }
+
+    #[test]
+    pub fn typed_sketch() {
+        let mut sketch_a = Sketch::<12, 4>::new().unwrap();
+        let mut sketch_b = Sketch::<12, 4>::new().unwrap();
+        for i in 3_000..3_010 {
+            sketch_a.add(i).unwrap();
+        }
+        for i in 3_002..3_012 {
+            sketch_b.add(i).unwrap();
+        }
+
+        assert_eq!(
+            sketch_a.add(4_096).unwrap_err(),
+            MinisketchError::ElementOutOfRange {
+                element: 4_096,
+                bits: 12
+            }
+        );
+
+        let message: [u8; Sketch::<12, 4>::SERIALIZED_SIZE] = sketch_a.to_bytes();
+        let sketch_a = Sketch::<12, 4>::from_bytes(&message).unwrap();
+
+        sketch_b.merge(&sketch_a).unwrap();
+        let (differences, num_differences) = sketch_b.decode_array().unwrap();
+        assert_eq!(num_differences, 4);
+        validate_elements(&differences.iter().map(|&e| e.into()).collect::<Vec<u64>>());
+
+        // Typed sketches can be converted from and into untyped ones
+        let untyped = sketch_b.into_minisketch();
+        assert!(Sketch::<12, 2>::try_from(untyped.clone()).is_err());
+        let sketch_b = Sketch::<12, 4>::try_from(untyped).unwrap();
+        assert_eq!(
+            sketch_b.decode_sorted().unwrap(),
+            vec![3_000, 3_001, 3_010, 3_011]
+        );
+
+        // Full-width elements are accepted as is
+        let mut sketch = Sketch::<64, 1>::new().unwrap();
+        sketch.add(u64::MAX).unwrap();
+        assert_eq!(sketch.decode_to_vec().unwrap(), vec![u64::MAX]);
+    }
+
+    #[test]
+    pub fn strict_insertion() {
+        let mut sketch = Minisketch::try_new(12, 0, 4).unwrap();
+        for &element in &[0, 4_096, u64::MAX] {
+            assert_eq!(
+                sketch.try_add(element).unwrap_err(),
+                MinisketchError::ElementOutOfRange { element, bits: 12 }
+            );
+        }
+        sketch.try_add(4_095).unwrap();
+        sketch.try_add(1).unwrap();
+        assert_eq!(sketch.decode_sorted().unwrap(), vec![1, 4_095]);
+
+        let mut sketch = Minisketch::try_new(64, 0, 1).unwrap();
+        sketch.try_add(u64::MAX).unwrap();
+        assert_eq!(sketch.decode_to_vec().unwrap(), vec![u64::MAX]);
+
+        // Offset sketches take elements from 0 to 2^bits - 2
+        let mut sketch_a = OffsetSketch::new(Minisketch::try_new(12, 0, 4).unwrap());
+        let mut sketch_b = OffsetSketch::new(Minisketch::try_new(12, 0, 4).unwrap());
+        sketch_a.try_add(0).unwrap();
+        sketch_a.try_add(4_094).unwrap();
+        sketch_a.try_add(7).unwrap();
+        sketch_b.try_add(7).unwrap();
+        assert!(sketch_a.try_add(4_095).is_err());
+
+        let _ = sketch_a.merge(&sketch_b).unwrap();
+        assert_eq!(sketch_a.decode_sorted().unwrap(), vec![0, 4_094]);
+
+        let mut sketch = OffsetSketch::new(Minisketch::try_new(64, 0, 1).unwrap());
+        assert!(sketch.try_add(u64::MAX).is_err());
+        sketch.try_add(u64::MAX - 1).unwrap();
+        assert_eq!(sketch.decode_to_vec().unwrap(), vec![u64::MAX - 1]);
+    }
+
+    #[test]
+    pub fn set_algebra() {
+        let sketches = (1..=4)
+            .map(|i| {
+                let mut sketch = Minisketch::try_new(12, 0, 4).unwrap();
+                sketch.add(i);
+                sketch.add(100);
+                sketch
+            })
+            .collect::<Vec<_>>();
+
+        let merged = &sketches[0] ^ &sketches[1];
+        assert_eq!(merged.decode_sorted().unwrap(), vec![1, 2]);
+        let merged = merged ^ &sketches[2];
+        assert_eq!(merged.decode_sorted().unwrap(), vec![1, 2, 3, 100]);
+        let merged = sketches[0].try_xor(&sketches[3]).unwrap();
+        assert_eq!(merged.decode_sorted().unwrap(), vec![1, 4]);
+
+        let merged: Option<Minisketch> = sketches.iter().sum();
+        assert_eq!(merged.unwrap().decode_sorted().unwrap(), vec![1, 2, 3, 4]);
+        let merged: Option<Minisketch> = sketches.clone().into_iter().sum();
+        assert_eq!(merged.unwrap().decode_sorted().unwrap(), vec![1, 2, 3, 4]);
+        let merged: Option<Minisketch> = sketches[..0].iter().sum();
+        assert!(merged.is_none());
+
+        let merged = Minisketch::try_merge_all(&sketches).unwrap().unwrap();
+        assert_eq!(merged.decode_sorted().unwrap(), vec![1, 2, 3, 4]);
+        assert!(Minisketch::try_merge_all(&[]).unwrap().is_none());
+
+        let other = Minisketch::try_new(16, 0, 4).unwrap();
+        assert!(sketches[0].try_xor(&other).is_err());
+        assert!(Minisketch::try_merge_all(vec![&sketches[0], &other]).is_err());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    pub fn incompatible_xor_panics_in_debug() {
+        let mut sketch = Minisketch::try_new(12, 0, 4).unwrap();
+        sketch ^= &Minisketch::try_new(16, 0, 4).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid sketch element")]
+    pub fn collect_rejects_wide_elements() {
+        let _: Sketch<12, 4> = vec![10u16, 1 << 12].into_iter().collect();
+    }
+
+    #[test]
+    pub fn batch_insertion() {
+        let elements = (1..5_000).collect::<Vec<u64>>();
+
+        let mut one_by_one = Minisketch::try_new(32, 0, 16).unwrap();
+        for &element in &elements {
+            one_by_one.add(element);
+        }
+        let mut expected = vec![0u8; one_by_one.serialized_size()];
+        one_by_one.serialize(&mut expected).unwrap();
+
+        let mut batched = Minisketch::try_new(32, 0, 16).unwrap();
+        batched.add_all(&elements);
+        batched.add_all(&[]);
+
+        let mut extended = Minisketch::try_new(32, 0, 16).unwrap();
+        extended.extend(elements.iter());
+
+        let params = SketchParams::new(32, 0, 16).unwrap();
+        let collected = Minisketch::from_iter_with(params, elements.clone()).unwrap();
+
+        for sketch in &[batched, extended, collected] {
+            let mut buf = vec![0u8; sketch.serialized_size()];
+            sketch.serialize(&mut buf).unwrap();
+            assert_eq!(buf, expected);
+        }
+
+        let typed: Sketch<32, 16> = (1..=3u32).collect();
+        assert_eq!(typed.decode_sorted().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    pub fn send_and_sync() {
+        use std::sync::Arc;
+        use std::thread;
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Minisketch>();
+        assert_send_sync::<OffsetSketch>();
+        assert_send_sync::<Sketch<12, 4>>();
+
+        // Sketches can be built on worker threads and moved back
+        let handles = (0..4u64)
+            .map(|i| {
+                thread::spawn(move || {
+                    let mut sketch = Minisketch::try_new(12, 0, 4).unwrap();
+                    sketch.add(3_000 + i);
+                    sketch
+                })
+            })
+            .collect::<Vec<_>>();
+        let sketches = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>();
+
+        // And be decoded and serialized concurrently through shared references
+        let merged = Arc::new(Minisketch::try_merge_all(&sketches).unwrap().unwrap());
+        let handles = (0..8)
+            .map(|_| {
+                let merged = Arc::clone(&merged);
+                thread::spawn(move || {
+                    let mut buf = vec![0u8; merged.serialized_size()];
+                    merged.serialize(&mut buf).unwrap();
+                    (
+                        merged.decode_sorted().unwrap(),
+                        buf,
+                        merged.clone().capacity(),
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut expected = vec![0u8; merged.serialized_size()];
+        merged.serialize(&mut expected).unwrap();
+        for handle in handles {
+            let (elements, buf, capacity) = handle.join().unwrap();
+            assert_eq!(elements, vec![3_000, 3_001, 3_002, 3_003]);
+            assert_eq!(buf, expected);
+            assert_eq!(capacity, 4);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    pub fn parallel_decoding() {
+        // Spread elements over the field, so that large differences don't decode by accident
+        let element = |i: u64| (i.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32) | 1;
+
+        let params = SketchParams::new(32, 0, 8).unwrap();
+        let local = Minisketch::from_iter_with(params, (1..=100).map(element)).unwrap();
+        let remotes = (1..32)
+            .map(|i| Minisketch::from_iter_with(params, (i..=100).map(element)).unwrap())
+            .collect::<Vec<_>>();
+
+        let results = Minisketch::decode_many(&remotes);
+        assert_eq!(results.len(), remotes.len());
+        for (remote, result) in remotes.iter().zip(results) {
+            assert_eq!(result, remote.decode_to_vec());
+        }
+
+        let pairs = remotes
+            .iter()
+            .map(|remote| (&local, remote))
+            .collect::<Vec<_>>();
+        for (i, result) in (1..).zip(Minisketch::reconcile_many(&pairs)) {
+            if i <= 9 {
+                let mut elements = result.unwrap();
+                elements.sort();
+                let mut expected = (1..i).map(element).collect::<Vec<_>>();
+                expected.sort();
+                assert_eq!(elements, expected);
+            } else {
+                assert_eq!(result, Err(MinisketchError::DecodeFailed { capacity: 8 }));
+            }
+        }
+
+        let other = Minisketch::try_new(12, 0, 8).unwrap();
+        assert!(Minisketch::reconcile_many(&[(&local, &other)])[0].is_err());
+    }
+
+    #[test]
+    pub fn cloning() {
+        let mut sketch = Minisketch::try_new(12, 0, 4).unwrap();
+        sketch.add(42);
+
+        let mut copy = sketch.try_clone().unwrap();
+        copy.add(10);
+        assert_eq!(sketch.decode_sorted().unwrap(), vec![42]);
+        assert_eq!(copy.decode_sorted().unwrap(), vec![10, 42]);
+        assert_eq!(copy.clone().decode_sorted().unwrap(), vec![10, 42]);
+    }
+
+    #[test]
+    pub fn decode_variants() {
+        let mut sketch = Minisketch::try_new(12, 0, 8).unwrap();
+        for i in &[30, 10, 20] {
+            sketch.add(*i);
+        }
+
+        let mut elements = sketch.decode_to_vec().unwrap();
+        elements.sort();
+        assert_eq!(elements, vec![10, 20, 30]);
+        assert_eq!(sketch.decode_sorted().unwrap(), vec![10, 20, 30]);
+
+        let (elements, num_elements) = sketch.decode_array::<3>().unwrap();
+        assert_eq!(num_elements, 3);
+        let mut elements = elements.to_vec();
+        elements.sort();
+        assert_eq!(elements, vec![10, 20, 30]);
+
+        // Sketch is decodable, but the buffer is too small
+        assert_eq!(
+            sketch.decode_array::<2>().unwrap_err(),
+            MinisketchError::DifferenceExceedsBuffer {
+                buffer: 2,
+                difference: 3
+            }
+        );
+
+        // Empty sketch decodes into nothing
+        let empty = Minisketch::try_new(12, 0, 8).unwrap();
+        assert!(empty.decode_to_vec().unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn sketch_params() {
+        use std::collections::HashSet;
+
+        let params = SketchParams::builder()
+            .bits(12)
+            .capacity(4)
+            .build()
+            .unwrap();
+        assert_eq!(params, SketchParams::new(12, 0, 4).unwrap());
+        assert_eq!(params.serialized_size(), 6);
+        assert_eq!(
+            params.to_string(),
+            "bits = 12, implementation = 0, capacity = 4"
+        );
+
+        let sketch = params.create_sketch().unwrap();
+        assert_eq!(sketch.params(), params);
+
+        let bigger = SketchParams::new(12, 0, 8).unwrap();
+        assert!(params.is_mergeable_with(&bigger));
+        assert!(sketch.is_mergeable_with(&bigger.create_sketch().unwrap()));
+        assert!(!sketch.is_mergeable_with(&Minisketch::try_new(16, 0, 4).unwrap()));
+
+        let set = [params, bigger, params]
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+        assert_eq!(set.len(), 2);
+
+        assert_eq!(
+            SketchParams::builder().bits(65).build().unwrap_err(),
+            MinisketchError::UnsupportedParameters {
+                bits: 65,
+                implementation: 0,
+                capacity: 1
+            }
+        );
+        assert!(SketchParams::new(12, 0, 0).is_err());
+        assert!(SketchParams::new(12, Minisketch::implementation_max() + 1, 4).is_err());
+        assert_eq!(
+            SketchParams::builder()
+                .bits(32)
+                .max_elements(10, 32)
+                .build()
+                .unwrap()
+                .capacity(),
+            Minisketch::compute_capacity(32, 10, 32)
+        );
+    }
+
+    #[test]
+    pub fn truncation_and_prefixes() {
+        let elements = [3000u64, 42, 10, 7, 1234];
+        let mut large = Minisketch::try_new(12, 0, 16).unwrap();
+        large.add_all(&elements);
+        let mut small = Minisketch::try_new(12, 0, 5).unwrap();
+        small.add_all(&elements);
+
+        let mut expected = vec![0u8; small.serialized_size()];
+        small.serialize(&mut expected).unwrap();
+
+        // 12 * 5 bits don't fill the last byte, so the prefix has to be masked
+        let mut prefix = vec![0xffu8; expected.len() + 1];
+        large.serialize_prefix(5, &mut prefix).unwrap();
+        assert_eq!(&prefix[..expected.len()], &expected[..]);
+        assert_eq!(prefix[expected.len()], 0xff);
+
+        let mut full = vec![0u8; large.serialized_size()];
+        large.serialize(&mut full).unwrap();
+        let mut restored = Minisketch::try_new(12, 0, 5).unwrap();
+        restored.deserialize_prefix(&full).unwrap();
+        let mut buf = vec![0u8; restored.serialized_size()];
+        restored.serialize(&mut buf).unwrap();
+        assert_eq!(buf, expected);
+        assert_eq!(
+            restored.deserialize_prefix(&full[..2]).unwrap_err(),
+            MinisketchError::BufferSize {
+                expected: 8,
+                actual: 2
+            }
+        );
+
+        assert_eq!(
+            small.serialize_prefix(6, &mut full).unwrap_err(),
+            MinisketchError::CapacityExceeded {
+                requested: 6,
+                capacity: 5
+            }
+        );
+
+        large.set_seed(7);
+        large.truncate(32).unwrap();
+        assert_eq!(large.capacity(), 16);
+        assert!(large.truncate(0).is_err());
+        large.truncate(5).unwrap();
+        assert_eq!(large.capacity(), 5);
+        assert_eq!(large.serialized_size(), 8);
+        large.serialize(&mut buf).unwrap();
+        assert_eq!(buf, expected);
+        assert_eq!(large.decode_sorted().unwrap(), vec![7, 10, 42, 1234, 3000]);
+    }
+
+    #[test]
+    pub fn sketch_extension() {
+        let scramble = |i: u64| (i.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 44) | 1;
+        let params = SketchParams::new(20, 0, 12).unwrap();
+        let local = Minisketch::from_iter_with(params, (0..100).map(scramble)).unwrap();
+        let remote = Minisketch::from_iter_with(params, (6..100).map(scramble)).unwrap();
+        let mut local = ExtendableSketch::new(local);
+        let remote = ExtendableSketch::new(remote);
+
+        assert!(local.reconcile().is_err());
+        local
+            .receive(5, &remote.initial_message(5).unwrap())
+            .unwrap();
+        assert_eq!(
+            local.reconcile().unwrap_err(),
+            MinisketchError::DecodeFailed { capacity: 5 }
+        );
+
+        // 5 syndromes of 20 bits end in the middle of a byte, so the extension is not byte-aligned
+        let message = remote.extension_message(5, 2).unwrap();
+        assert_eq!(message.len(), 5);
+        assert_eq!(
+            local.receive(2, &message[1..]).unwrap_err(),
+            MinisketchError::BufferSize {
+                expected: 5,
+                actual: 4
+            }
+        );
+        local.receive(2, &message).unwrap();
+        assert_eq!(local.received_capacity(), 7);
+
+        let mut differences = local.reconcile().unwrap();
+        differences.sort();
+        let mut expected = (0..6).map(scramble).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(differences, expected);
+
+        // Concatenated messages are the same as a single message of all syndromes
+        let mut at_once = ExtendableSketch::new(local.as_minisketch().clone());
+        at_once
+            .receive(7, &remote.initial_message(7).unwrap())
+            .unwrap();
+        assert_eq!(at_once.reconcile().unwrap().len(), 6);
+
+        assert_eq!(
+            remote.extension_message(10, 3).unwrap_err(),
+            MinisketchError::CapacityExceeded {
+                requested: 13,
+                capacity: 12
+            }
+        );
+        assert!(local.receive(6, &[0u8; 15]).is_err());
+
+        local.clear_received();
+        assert_eq!(local.received_capacity(), 0);
+    }
+
+    #[test]
+    pub fn implementation_selection() {
+        let configurations = Minisketch::supported_configurations();
+        assert!(configurations.contains(&(12, 0)));
+        assert!(configurations
+            .iter()
+            .all(|&(bits, _)| (2..=64).contains(&bits)));
+        for &(bits, implementation) in &configurations {
+            assert!(Minisketch::try_new(bits, implementation, 1).is_ok());
+        }
+
+        #[cfg(feature = "std")]
+        {
+            let best = Minisketch::best_implementation(12).unwrap();
+            assert!(configurations.contains(&(12, best)));
+            assert_eq!(Minisketch::best_implementation(12), Some(best));
+            assert_eq!(Minisketch::best_implementation(0), None);
+            assert_eq!(Minisketch::best_implementation(65), None);
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "pure-rust"))]
+    pub fn field_selection() {
+        for bits in 0..=65 {
+            let supported = Minisketch::bits_supported(bits);
+            if !minisketch_sys::INSTALLED_LIB {
+                assert_eq!(supported, ffi_sketch::field_selected(bits));
+            }
+            assert_eq!(Minisketch::try_new(bits, 0, 1).is_ok(), supported);
+        }
+    }
+
+    /// Returns elements that are spread over all 64 bits, and truncated by sketches.
+    fn spread_elements(count: u64) -> Vec<u64> {
+        (1..=count)
+            .map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+            .collect()
+    }
+
+    #[test]
+    pub fn native_sketches() {
+        use native::NativeSketch;
+        use std::collections::BTreeSet;
+
+        assert!(NativeSketch::new(1, 0, 8).is_none());
+        assert!(NativeSketch::new(65, 0, 8).is_none());
+        assert!(NativeSketch::new(12, 1, 8).is_none());
+        assert!(NativeSketch::new(12, 0, 0).is_none());
+
+        for bits in 2..=64 {
+            let mask = u64::MAX >> (64 - bits);
+            let elements = spread_elements(8)
+                .into_iter()
+                .map(|element| element & mask)
+                .filter(|&element| element != 0)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>();
+
+            let mut sketch = NativeSketch::new(bits, 0, 8).unwrap();
+            sketch.add_all(&elements);
+            let mut buf = vec![0u8; sketch.serialized_size()];
+            sketch.serialize(&mut buf);
+
+            let mut restored = NativeSketch::new(bits, 0, 8).unwrap();
+            restored.deserialize(&buf);
+            let mut decoded = [0u64; 8];
+            let num_elements = restored.decode(&mut decoded).unwrap();
+            let mut decoded = decoded[..num_elements].to_vec();
+            decoded.sort_unstable();
+            assert_eq!(decoded, elements);
+
+            // Decoding stops as soon as the difference doesn't fit into the buffer
+            assert!(restored
+                .decode(&mut [0u64; 8][..num_elements - 1])
+                .is_none());
+        }
+    }
+
+    #[test]
+    pub fn known_answers() {
+        // Sketches of capacity 3 with `spread_elements(3)`, serialized. These vectors were not
+        // produced by either backend, but by a separate implementation of the sketch math.
+        let serialized: [(u32, &str); 63] = [
+            (2, "04"),
+            (3, "7801"),
+            (4, "9007"),
+            (5, "c075"),
+            (6, "00db01"),
+            (7, "808615"),
+            (8, "0021ac"),
+            (9, "00b46302"),
+            (10, "00c0752a"),
+            (11, "0010bdd800"),
+            (12, "00b0b27a04"),
+            (13, "00f002fd2b"),
+            (14, "00b0e01a4303"),
+            (15, "00f0cc0fed08"),
+            (16, "00f08d4bdd83"),
+            (17, "00f0df17eb9706"),
+            (18, "00f01dfcb94227"),
+            (19, "00f04176fe855601"),
+            (20, "00f021fd7df9b50e"),
+            (21, "00f00141c948c823"),
+            (22, "00f081517f18fa0902"),
+            (23, "00f08178aed7e61217"),
+            (24, "00f0015adf3967f1a0"),
+            (25, "00f00166ea91f18b1801"),
+            (26, "00f001442d283b208b2a"),
+            (27, "00f0015419864708039000"),
+            (28, "00f0010cc632f774148b04"),
+            (29, "00f0017caf23cfdf6eec14"),
+            (30, "00f001bcb73d9451928bbb03"),
+            (31, "00f001fce7758222c49a1a0f"),
+            (32, "00f001fca10c215d7ec15056"),
+            (33, "00f001fc952ed83dac5b0a4f05"),
+            (34, "00f001fc833a4067622f274c01"),
+            (35, "00f001fc47181d87e6fa89a82500"),
+            (36, "00f001fcd78862bfcc949b6aef07"),
+            (37, "00f001fc67da9d167189cf8dcc70"),
+            (38, "00f001fce750bd50fae586c4c36302"),
+            (39, "00f001fc67e3942574924d8b8c3215"),
+            (40, "00f001fce76f032f4ea80ed563ce4a"),
+            (41, "00f001fce76b3829218064750fc95501"),
+            (42, "00f001fce7dbf624c9a4378cdad0f92d"),
+            (43, "00f001fce76fc3e7fa8ce39bf6d2f76800"),
+            (44, "00f001fce7877e112424b83f17bf674804"),
+            (45, "00f001fce7476faa2faa7eeed469976b7c"),
+            (46, "00f001fce727aaa79559e8149b66ee204e01"),
+            (47, "00f001fce7e7a94d71a97fb37db09a26f60e"),
+            (48, "00f001fce7e75ddda6799503fe0bfc986ea6"),
+            (49, "00f001fce7e7a7c9622db90d5c84f0ce8ef402"),
+            (50, "00f001fce7e7774fa3aed3400ddc93f789b508"),
+            (51, "00f001fce7e787c4644033c10d3fb460a3960201"),
+            (52, "00f001fce7e76f70e54ff0e4a5f3cc2bfd776b0a"),
+            (53, "00f001fce7e79fca1b59b4ecb38fc1d3342c797d"),
+            (54, "00f001fce7e7ff799ea992392fc06699b8528b1b00"),
+            (55, "00f001fce7e77f8e8d47444988474e1c78d331f901"),
+            (56, "00f001fce7e7ff767c49c4cb23d6c7c09b2b470130"),
+            (57, "00f001fce7e7ff0087778dd8f731bc9335367a279006"),
+            (58, "00f001fce7e7fffcf2687be647737c9db2e50c49b600"),
+            (59, "00f001fce7e7ff50d7842977a9dd9c682885c4f5dcde00"),
+            (60, "00f001fce7e7ff48795bcd73478fa62f318c4a3762f20e"),
+            (61, "00f001fce7e7ff981277efa7b5173610b738e3e4897c59"),
+            (62, "00f001fce7e7ff3868fa1b53ccd2c1b5d68284dae69af102"),
+            (63, "00f001fce7e7fff804334aea14291242bba3a07e40a60400"),
+            (64, "00f001fce7e7ff788f997330f70e67a6e257312b73c77521"),
+        ];
+        let hex = |buf: &[u8]| buf.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+
+        let elements = spread_elements(3);
+        for &(bits, expected) in serialized
+            .iter()
+            .filter(|&&(bits, _)| Minisketch::bits_supported(bits))
+        {
+            let params = SketchParams::new(bits, 0, 3).unwrap();
+            let sketch = Minisketch::from_iter_with(params, elements.clone()).unwrap();
+            let mut buf = vec![0u8; sketch.serialized_size()];
+            sketch.serialize(&mut buf).unwrap();
+            assert_eq!(hex(&buf), expected, "bits = {}", bits);
+
+            let restored = Minisketch::from_bytes(bits, 0, 3, &buf).unwrap();
+            let mut truncated = elements
+                .iter()
+                .map(|&element| element & (u64::MAX >> (64 - bits)))
+                .collect::<Vec<_>>();
+            truncated.sort_unstable();
+            for &decoder in &[Decoder::Backend, Decoder::Fast] {
+                let mut decoded = vec![0u64; 3];
+                let num_decoded = restored.decode_with(&mut decoded, decoder).unwrap();
+                decoded[..num_decoded].sort_unstable();
+                assert_eq!(decoded[..num_decoded], truncated[..], "bits = {}", bits);
+            }
+        }
+
+        // Sketches of capacity 2 with `spread_elements(5)`, beyond capacity unless truncated
+        // elements collide. Whether they decode, and into what set, is the same for any decoder.
+        let decoded: [(u32, Option<&[u64]>); 15] = [
+            (2, Some(&[2, 3])),
+            (3, Some(&[3, 6])),
+            (4, Some(&[13])),
+            (5, Some(&[7, 26])),
+            (6, Some(&[17, 44])),
+            (7, Some(&[1, 60])),
+            (8, Some(&[192, 253])),
+            (9, Some(&[349, 352])),
+            (10, None),
+            (11, None),
+            (12, None),
+            (13, Some(&[1802, 2871])),
+            (14, None),
+            (15, None),
+            (16, None),
+        ];
+
+        let elements = spread_elements(5);
+        for &(bits, expected) in decoded
+            .iter()
+            .filter(|&&(bits, _)| Minisketch::bits_supported(bits))
+        {
+            let params = SketchParams::new(bits, 0, 2).unwrap();
+            let sketch = Minisketch::from_iter_with(params, elements.clone()).unwrap();
+            for &decoder in &[Decoder::Backend, Decoder::Fast] {
+                let mut decoded = vec![0u64; 2];
+                let result = sketch
+                    .decode_with(&mut decoded, decoder)
+                    .ok()
+                    .map(|num_decoded| {
+                        decoded.truncate(num_decoded);
+                        decoded.sort_unstable();
+                        decoded
+                    });
+                assert_eq!(result.as_deref(), expected, "bits = {}", bits);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "pure-rust"))]
+    pub fn native_matches_libminisketch() {
+        use native::NativeSketch;
+
+        let serialize = |sketch: &NativeSketch| {
+            let mut buf = vec![0u8; sketch.serialized_size()];
+            sketch.serialize(&mut buf);
+            buf
+        };
+        let decode = |sketch: &NativeSketch| {
+            let mut elements = vec![0u64; sketch.capacity()];
+            sketch.decode(&mut elements).map(|num_elements| {
+                elements.truncate(num_elements);
+                elements.sort_unstable();
+                elements
+            })
+        };
+
+        let elements = spread_elements(20);
+        for bits in (2..=64).filter(|&bits| Minisketch::bits_supported(bits)) {
+            let params = SketchParams::new(bits, 0, 12).unwrap();
+            let mut sketch = Minisketch::from_iter_with(params, elements[..10].to_vec()).unwrap();
+            let mut native = NativeSketch::new(bits, 0, 12).unwrap();
+            native.add_all(&elements[..10]);
+
+            let mut expected = vec![0u8; sketch.serialized_size()];
+            sketch.serialize(&mut expected).unwrap();
+            assert_eq!(serialize(&native), expected, "bits = {}", bits);
+
+            let mut restored = NativeSketch::new(bits, 0, 12).unwrap();
+            restored.deserialize(&expected);
+            assert_eq!(
+                decode(&restored),
+                sketch.decode_sorted().ok(),
+                "bits = {}",
+                bits
+            );
+
+            // Difference of 15 elements exceeds the merged capacity of 8
+            let params = SketchParams::new(bits, 0, 8).unwrap();
+            let other = Minisketch::from_iter_with(params, elements[5..].to_vec()).unwrap();
+            let mut native_other = NativeSketch::new(bits, 0, 8).unwrap();
+            native_other.add_all(&elements[5..]);
+
+            let _ = sketch.merge(&other).unwrap();
+            assert_eq!(native.merge(&native_other), 8);
+            let mut expected = vec![0u8; sketch.serialized_size()];
+            sketch.serialize(&mut expected).unwrap();
+            assert_eq!(serialize(&native), expected, "bits = {}", bits);
+            assert_eq!(
+                decode(&native),
+                sketch.decode_sorted().ok(),
+                "bits = {}",
+                bits
+            );
+        }
+    }
+
+    #[test]
+    pub fn field_arithmetic() {
+        use crate::field::{self, Field, Gf2b};
+
+        fn check<const BITS: u32>()
+        where
+            typed::Bits<BITS>: typed::SupportedBits,
+        {
+            let field = Field::new(BITS).unwrap();
+            let elements = spread_elements(20)
+                .into_iter()
+                .map(Gf2b::<BITS>::from_truncated)
+                .collect::<Vec<_>>();
+
+            for &a in &elements {
+                assert_eq!(a.square(), a * a);
+                assert_eq!(a.pow(3), a * a * a);
+                assert_eq!(a.pow(0), Gf2b::ONE);
+                // Every element is a root of x^(2^BITS) - x
+                assert_eq!(a.pow(1 << (BITS - 1)).square(), a);
+                if !a.is_zero() {
+                    assert_eq!(a.inverse().map(|inverse| a * inverse), Some(Gf2b::ONE));
+                }
+
+                for &b in &elements {
+                    assert_eq!(a * b, b * a);
+                    assert_eq!((a + b).square(), a.square() + b.square());
+                    assert_eq!((a + b).trace(), a.trace() ^ b.trace());
+                    assert_eq!((a * b).value(), field.mul(a.value(), b.value()));
+                }
+            }
+
+            assert_eq!(Gf2b::<BITS>::ZERO.inverse(), None);
+            assert_eq!(Gf2b::<BITS>::ZERO.pow(0), Gf2b::ONE);
+            assert!(!Gf2b::<BITS>::ZERO.trace());
+            assert!(Gf2b::<BITS>::new(u64::MAX >> (64 - BITS)).is_some());
+        }
+
+        check::<2>();
+        check::<7>();
+        check::<8>();
+        check::<13>();
+        check::<32>();
+        check::<33>();
+        check::<58>();
+        check::<62>();
+        check::<64>();
+        assert_eq!(Gf2b::<8>::MODULUS, 0x1b);
+        assert_eq!(Gf2b::<63>::new(1 << 63), None);
+
+        // Hardware carry-less multiplication, if any, agrees with the portable one
+        for &a in &spread_elements(20) {
+            for &b in &spread_elements(20) {
+                assert_eq!(field::clmul(a, b), field::clmul_portable(a, b));
+            }
+        }
+    }
+
+    #[test]
+    pub fn field_known_answers() {
+        use crate::field::{Field, Gf2b};
+
+        /// Checks `(a, b, a * b, 1 / a, trace(a))` vectors, which were computed by a separate
+        /// implementation of the field arithmetic with the same moduli.
+        fn check<const BITS: u32>(vectors: &[(u64, u64, u64, u64, bool)])
+        where
+            typed::Bits<BITS>: typed::SupportedBits,
+        {
+            let field = Field::new(BITS).unwrap();
+            for &(a, b, product, inverse, trace) in vectors {
+                let (a, b) = (Gf2b::<BITS>::new(a).unwrap(), Gf2b::<BITS>::new(b).unwrap());
+                assert_eq!((a * b).value(), product, "bits = {}", BITS);
+                assert_eq!(field.mul(a.value(), b.value()), product, "bits = {}", BITS);
+                assert_eq!(
+                    a.inverse().map(Gf2b::value),
+                    Some(inverse),
+                    "bits = {}",
+                    BITS
+                );
+                assert_eq!(a.trace(), trace, "bits = {}", BITS);
+            }
+        }
+
+        check::<2>(&[(1, 3, 3, 1, false), (2, 1, 2, 3, true), (3, 2, 1, 2, true)]);
+        check::<3>(&[(5, 6, 3, 2, true), (2, 3, 6, 5, false), (7, 5, 6, 4, true)]);
+        check::<5>(&[
+            (21, 30, 31, 26, true),
+            (10, 19, 15, 25, true),
+            (31, 8, 3, 27, false),
+        ]);
+        check::<7>(&[
+            (21, 126, 114, 10, true),
+            (42, 19, 81, 5, false),
+            (63, 40, 12, 6, true),
+        ]);
+        check::<8>(&[
+            (21, 126, 60, 43, false),
+            (42, 147, 52, 152, true),
+            (63, 168, 240, 25, true),
+        ]);
+        check::<12>(&[
+            (3093, 2174, 263, 3163, false),
+            (2090, 1171, 1925, 2788, false),
+            (1087, 168, 2283, 2809, false),
+        ]);
+        check::<13>(&[
+            (7189, 2174, 1246, 2910, true),
+            (6186, 1171, 8125, 7583, false),
+            (5183, 168, 8003, 5866, true),
+        ]);
+        check::<16>(&[
+            (31765, 59518, 54658, 4518, false),
+            (63530, 25747, 55222, 2259, true),
+            (29759, 57512, 39636, 53873, true),
+        ]);
+        check::<24>(&[
+            (0x4a7c15, 0xbee87e, 0xc70a4b, 0x651a04, false),
+            (0x94f82a, 0x96493, 0x77a09d, 0x328d02, true),
+            (0xdf743f, 0x53e0a8, 0x6c423b, 0x82253f, true),
+        ]);
+        check::<31>(&[
+            (0x7f4a7c15, 0x7bbee87e, 0x6c483e12, 0x2d2dbff4, true),
+            (0x7e94f82a, 0x7b096493, 0x4269d461, 0x687f99f1, false),
+            (0x7ddf743f, 0x7a53e0a8, 0x5cb8a3b3, 0x56b720ff, true),
+        ]);
+        check::<32>(&[
+            (0x7f4a7c15, 0xfbbee87e, 0xc1959bef, 0xfcf6e3ac, false),
+            (0xfe94f82a, 0x7b096493, 0x55d67fc8, 0x7e7b71d6, false),
+            (0x7ddf743f, 0xfa53e0a8, 0x9a73601f, 0x654b26f7, true),
+        ]);
+        check::<33>(&[
+            (0x17f4a7c15, 0xfbbee87e, 0xd19e3a39, 0x67461815, true),
+            (0xfe94f82a, 0x7b096493, 0x1ec172932, 0x8f1d48cc, true),
+            (0x7ddf743f, 0x1fa53e0a8, 0x46451333, 0x65e009b0, false),
+        ]);
+        check::<48>(&[
+            (
+                0x79b97f4a7c15,
+                0xda58fbbee87e,
+                0xe2982f30c3c0,
+                0x81e2c8d48a24,
+                false,
+            ),
+            (
+                0xf372fe94f82a,
+                0x54127b096493,
+                0xc012243a10af,
+                0x40f1646a4512,
+                true,
+            ),
+            (
+                0x6d2c7ddf743f,
+                0xcdcbfa53e0a8,
+                0xb5f2dc1b8ae7,
+                0x356abdd87793,
+                false,
+            ),
+        ]);
+        check::<58>(&[
+            (
+                0x23779b97f4a7c15,
+                0x14cda58fbbee87e,
+                0x1e0e93aed0fdd1d,
+                0x2fb1f7bf8874636,
+                true,
+            ),
+            (
+                0x6ef372fe94f82a,
+                0x38454127b096493,
+                0x4aa792e3e0e287,
+                0x18c7b71fa2da826,
+                false,
+            ),
+            (
+                0x2a66d2c7ddf743f,
+                0x1bbcdcbfa53e0a8,
+                0x367a43afe07dc79,
+                0x3606b1b77efce06,
+                false,
+            ),
+        ]);
+        check::<62>(&[
+            (
+                0x1e3779b97f4a7c15,
+                0x354cda58fbbee87e,
+                0x118e56529bc259f0,
+                0x2635b4370209f0a5,
+                false,
+            ),
+            (
+                0x3c6ef372fe94f82a,
+                0x138454127b096493,
+                0x10bbe11c60d38086,
+                0x331ada1b9104f852,
+                true,
+            ),
+            (
+                0x1aa66d2c7ddf743f,
+                0x31bbcdcbfa53e0a8,
+                0x3be176c1be9da03a,
+                0x14d8cd801ecf23e4,
+                false,
+            ),
+        ]);
+        check::<63>(&[
+            (
+                0x1e3779b97f4a7c15,
+                0x354cda58fbbee87e,
+                0x6fd4afcbb646c764,
+                0x5a858fde1b1be26,
+                true,
+            ),
+            (
+                0x3c6ef372fe94f82a,
+                0x538454127b096493,
+                0x7d6248a3c74c8444,
+                0x2d42c7ef0d8df13,
+                false,
+            ),
+            (
+                0x5aa66d2c7ddf743f,
+                0x71bbcdcbfa53e0a8,
+                0x59de237458009128,
+                0x764b01ee90a69b88,
+                true,
+            ),
+        ]);
+        check::<64>(&[
+            (
+                0x9e3779b97f4a7c15,
+                0xb54cda58fbbee87e,
+                0xda10c4420e729e09,
+                0xd9f1e2e84cca0d11,
+                true,
+            ),
+            (
+                0x3c6ef372fe94f82a,
+                0x538454127b096493,
+                0xefb23b57dd6d807b,
+                0xe7d1490ca4a693ac,
+                true,
+            ),
+            (
+                0xdaa66d2c7ddf743f,
+                0xf1bbcdcbfa53e0a8,
+                0xe8e85014d0d5176f,
+                0xe77c255d01134110,
+                true,
+            ),
+        ]);
+    }
+
+    #[test]
+    pub fn fast_decoding() {
+        use native::NativeSketch;
+
+        // Pseudorandom bytes, to get sketches over capacity as well
+        let mut state = 0u64;
+        let mut random_bytes = |len: usize| {
+            (0..len)
+                .map(|_| {
+                    state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                    (state.wrapping_mul(0xbf58_476d_1ce4_e5b9) >> 56) as u8
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let elements = spread_elements(600);
+        for &(bits, capacity) in &[
+            (2, 3),
+            (3, 6),
+            (5, 20),
+            (8, 40),
+            (12, 100),
+            (32, 300),
+            (64, 150),
+        ] {
+            let mut messages = vec![random_bytes(
+                NativeSketch::new(bits, 0, capacity)
+                    .unwrap()
+                    .serialized_size(),
+            )];
+            for &count in &[0, 1, capacity / 2, capacity, capacity + 1, 2 * capacity] {
+                let mut sketch = NativeSketch::new(bits, 0, capacity).unwrap();
+                sketch.add_all(&elements[..count]);
+                let mut message = vec![0u8; sketch.serialized_size()];
+                sketch.serialize(&mut message);
+                messages.push(message);
+            }
+
+            for message in &messages {
+                let mut sketch = NativeSketch::new(bits, 0, capacity).unwrap();
+                sketch.deserialize(message);
+
+                // Buffer size bounds the degree of the locator, even beyond capacity
+                for &max_elements in &[capacity / 2, capacity, 2 * capacity] {
+                    let mut expected = vec![0u64; max_elements];
+                    let mut decoded = vec![0u64; max_elements];
+                    let expected_len = sketch.decode(&mut expected);
+                    let decoded_len = decoder::decode(bits, capacity, message, &mut decoded);
+
+                    assert_eq!(
+                        decoded_len, expected_len,
+                        "bits = {}, capacity = {}",
+                        bits, capacity
+                    );
+                    assert_eq!(
+                        decoded, expected,
+                        "bits = {}, capacity = {}",
+                        bits, capacity
+                    );
+                }
+            }
+        }
+
+        // Small fields often have sketches over capacity that still decode into something
+        for _ in 0..200 {
+            let message = random_bytes(4);
+            let mut sketch = NativeSketch::new(4, 0, 8).unwrap();
+            sketch.deserialize(&message);
+
+            let mut expected = vec![0u64; 16];
+            let mut decoded = vec![0u64; 16];
+            assert_eq!(
+                decoder::decode(4, 8, &message, &mut decoded),
+                sketch.decode(&mut expected)
+            );
+            assert_eq!(decoded, expected);
+        }
+
+        for bits in (2..=64).filter(|&bits| Minisketch::bits_supported(bits)) {
+            let params = SketchParams::new(bits, 0, 64).unwrap();
+            let sketch = Minisketch::from_iter_with(params, elements[..40].to_vec()).unwrap();
+            let mut decoded = sketch.decode_to_vec().unwrap();
+            let mut fast = vec![0u64; 64];
+            let num_elements = sketch.decode_with(&mut fast, Decoder::Fast).unwrap();
+            fast.truncate(num_elements);
+            decoded.sort_unstable();
+            fast.sort_unstable();
+            assert_eq!(fast, decoded, "bits = {}", bits);
+
+            let mut small = [0u64; 10];
+            assert_eq!(
+                sketch.decode_with(&mut small, Decoder::Fast),
+                sketch.decode(&mut small)
+            );
+        }
+    }
+
+    #[test]
+    pub fn detailed_decoding() {
+        use native::NativeSketch;
+
+        let elements = spread_elements(200)
+            .into_iter()
+            .map(|element| element >> 32)
+            .collect::<Vec<_>>();
+
+        for &capacity in &[1, 4, 16, 40] {
+            for difference in 0..=3 * capacity {
+                let params = SketchParams::new(32, 0, capacity).unwrap();
+                let sketch =
+                    Minisketch::from_iter_with(params, elements[..difference].to_vec()).unwrap();
+                let mut decoded = vec![0u64; capacity];
+                let result = sketch.decode_detailed(&mut decoded);
+                if difference <= capacity {
+                    assert_eq!(result, Ok(difference));
+                    continue;
+                }
+
+                let (locator_degree, min_difference) = match result {
+                    Err(MinisketchError::DifferenceExceedsCapacity {
+                        capacity: failed_capacity,
+                        locator_degree,
+                        min_difference,
+                    }) => {
+                        assert_eq!(failed_capacity, capacity);
+                        (locator_degree, min_difference)
+                    }
+                    // Sketches of small capacity occasionally decode into wrong elements
+                    Ok(num_elements) if capacity < 4 => {
+                        assert_ne!(num_elements, difference);
+                        continue;
+                    }
+                    other => panic!("Unexpected result {:?}", other),
+                };
+                assert!(locator_degree <= difference && locator_degree <= 2 * capacity);
+                assert!(min_difference > capacity && min_difference <= difference);
+
+                // Berlekamp-Massey finds a recurrence of the same length whenever it's a locator
+                let mut native = NativeSketch::new(32, 0, capacity).unwrap();
+                native.add_all(&elements[..difference]);
+                let mut buf = vec![0u64; 2 * capacity];
+                if let Some(roots) = native.decode(&mut buf) {
+                    assert_eq!(roots, locator_degree);
+                }
+            }
+        }
+
+        // Undersized buffers still get the same error as from decode
+        let params = SketchParams::new(32, 0, 8).unwrap();
+        let sketch = Minisketch::from_iter_with(params, elements[..4].to_vec()).unwrap();
+        let mut small = [0u64; 2];
+        assert_eq!(
+            sketch.decode_detailed(&mut small),
+            sketch.decode(&mut small)
+        );
+    }
+
+    #[test]
+    pub fn array_decoding() {
+        use native::NativeSketch;
+
+        /// Checks that heap-free decoding into `N` elements matches decoding into a slice.
+        fn check<const N: usize>(sketch: &NativeSketch) {
+            let mut expected = [0u64; N];
+            let mut decoded = [0u64; N];
+            assert_eq!(
+                sketch.decode_array(&mut decoded),
+                sketch.decode(&mut expected),
+                "bits = {}, capacity = {}",
+                sketch.bits(),
+                sketch.capacity()
+            );
+            assert_eq!(decoded, expected);
+        }
+
+        let elements = spread_elements(100);
+        let mut state = 0u64;
+        for &(bits, capacity) in &[(2, 3), (4, 8), (8, 20), (12, 10), (32, 24), (64, 12)] {
+            for count in 0..=2 * capacity {
+                let mut sketch = NativeSketch::new(bits, 0, capacity).unwrap();
+                sketch.add_all(&elements[..count]);
+                check::<0>(&sketch);
+                check::<5>(&sketch);
+                check::<12>(&sketch);
+                check::<24>(&sketch);
+                check::<48>(&sketch);
+
+                // Pseudorandom sketches of small fields often decode into something
+                let mut message = vec![0u8; sketch.serialized_size()];
+                for byte in &mut message {
+                    state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                    *byte = (state.wrapping_mul(0xbf58_476d_1ce4_e5b9) >> 56) as u8;
+                }
+                sketch.deserialize(&message);
+                check::<12>(&sketch);
+                check::<48>(&sketch);
+            }
+        }
+
+        let params = SketchParams::new(32, 0, 16).unwrap();
+        let sketch = Minisketch::from_iter_with(params, (1..=10).map(|i| i * 12345)).unwrap();
+        let (decoded, num_elements) = sketch.decode_array::<16>().unwrap();
+        let mut decoded = decoded[..num_elements].to_vec();
+        decoded.sort_unstable();
+        assert_eq!(decoded, sketch.decode_sorted().unwrap());
+        assert_eq!(
+            sketch.decode_array::<4>().unwrap_err(),
+            MinisketchError::DifferenceExceedsBuffer {
+                buffer: 4,
+                difference: 10
+            }
+        );
+
+        let params = SketchParams::new(32, 0, 4).unwrap();
+        let sketch = Minisketch::from_iter_with(params, (1..=10).map(|i| i * 12345)).unwrap();
+        assert_eq!(
+            sketch.decode_array::<8>().unwrap_err(),
+            MinisketchError::DecodeFailed { capacity: 4 }
+        );
+    }
 }
@@ -0,0 +1,619 @@
+//! Sketches implemented in Rust, bit-compatible with `libminisketch`.
+//!
+//! A sketch of capacity `c` holds the odd power sums `s_1, s_3, ..., s_(2c-1)` of its elements,
+//! where `s_i` is the sum of `e^i` over all elements `e`. Even power sums follow from them, since
+//! `s_2i = s_i^2` in characteristic 2. Decoding finds the error locator polynomial of the power
+//! sums with the Berlekamp-Massey algorithm, and the roots of its reverse are the elements.
+//!
+//! This follows upstream `sketch_impl.h`, so serialized sketches are the same, and so is the set
+//! of decoded elements. Their order may differ: `libminisketch` randomizes root finding with the
+//! seed of a sketch, while root finding here is deterministic and ignores the seed.
+
+// Unless it is the backend, only compatibility tests use this module
+#![cfg_attr(not(feature = "pure-rust"), allow(dead_code))]
+
+use crate::decoder::berlekamp_massey;
+use crate::field::Field;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Sketch of `bits`-bit elements that holds one power sum per element of capacity.
+#[derive(Debug, Clone)]
+pub(crate) struct NativeSketch {
+    field: Field,
+    syndromes: Vec<u64>,
+}
+
+impl NativeSketch {
+    /// Returns `true` if sketches of `bits`-bit elements are supported, i.e. for 2 to 64 bits.
+    pub(crate) fn bits_supported(bits: u32) -> bool {
+        Field::new(bits).is_some()
+    }
+
+    /// There's a single implementation that supports all element sizes.
+    pub(crate) fn implementation_max() -> u32 {
+        0
+    }
+
+    /// Creates an empty sketch, or returns `None` if parameters are unsupported or the syndromes
+    /// can't be allocated.
+    pub(crate) fn new(bits: u32, implementation: u32, capacity: usize) -> Option<Self> {
+        if implementation != 0 || capacity == 0 {
+            return None;
+        }
+
+        let field = Field::new(bits)?;
+        let mut syndromes = Vec::new();
+        syndromes.try_reserve_exact(capacity).ok()?;
+        syndromes.resize(capacity, 0);
+
+        Some(NativeSketch { field, syndromes })
+    }
+
+    pub(crate) fn bits(&self) -> u32 {
+        self.field.bits()
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.syndromes.len()
+    }
+
+    pub(crate) fn implementation(&self) -> u32 {
+        0
+    }
+
+    pub(crate) fn serialized_size(&self) -> usize {
+        (self.bits() as usize * self.capacity()).div_ceil(8)
+    }
+
+    /// Adds the element represented by the `bits` least significant bits of `element`.
+    pub(crate) fn add(&mut self, element: u64) {
+        let element = self.field.truncate(element);
+        if element == 0 {
+            return;
+        }
+
+        let square = self.field.sqr(element);
+        let mut power = element;
+        for syndrome in &mut self.syndromes {
+            *syndrome ^= power;
+            power = self.field.mul(power, square);
+        }
+    }
+
+    pub(crate) fn add_all(&mut self, elements: &[u64]) {
+        for &element in elements {
+            self.add(element);
+        }
+    }
+
+    /// Seed randomizes root finding in `libminisketch`. Root finding here is deterministic, and
+    /// its cost is bounded regardless of the elements, so there's nothing to randomize.
+    pub(crate) fn set_seed(&mut self, _seed: u64) {}
+
+    pub(crate) fn try_clone(&self) -> Option<Self> {
+        Some(self.clone())
+    }
+
+    /// Merges `other` into this sketch and returns the resulting capacity, or 0 if the sketches
+    /// differ in their element size.
+    pub(crate) fn merge(&mut self, other: &Self) -> usize {
+        if self.field != other.field {
+            return 0;
+        }
+
+        self.syndromes.truncate(other.capacity());
+        for (syndrome, other) in self.syndromes.iter_mut().zip(&other.syndromes) {
+            *syndrome ^= other;
+        }
+
+        self.capacity()
+    }
+
+    /// Decodes at most `elements.len()` elements into `elements`, and returns their number, or
+    /// `None` if decoding failed.
+    pub(crate) fn decode(&self, elements: &mut [u64]) -> Option<usize> {
+        let locator = berlekamp_massey(&self.field, &self.all_syndromes(), elements.len())?;
+
+        // Locator is the product of (1 - e * x) over elements, so its reverse has them as roots
+        let mut poly = locator;
+        poly.reverse();
+        let roots = find_roots(&self.field, &poly)?;
+        elements[..roots.len()].copy_from_slice(&roots);
+
+        Some(roots.len())
+    }
+
+    /// Decodes at most `N` elements the same way as [`decode`], in the same order, but without
+    /// allocating.
+    ///
+    /// Polynomials of degree up to `N` fit into arrays of `N` coefficients, since the locator has
+    /// a constant term of 1 and its reverse, as well as its factors, are monic. Even power sums
+    /// are squared from odd ones when needed instead of being stored.
+    ///
+    /// [`decode`]: #method.decode
+    pub(crate) fn decode_array<const N: usize>(&self, elements: &mut [u64; N]) -> Option<usize> {
+        let mut locator = [0; N];
+        let degree = self.berlekamp_massey_array(&mut locator)?;
+
+        // Reverse of the locator, without its leading coefficient
+        let mut poly = [0; N];
+        for (p, &l) in poly[..degree]
+            .iter_mut()
+            .zip(locator[..degree].iter().rev())
+        {
+            *p = l;
+        }
+        find_roots_array(&self.field, poly, degree, elements)?;
+
+        Some(degree)
+    }
+
+    /// Writes `bits` bits of every syndrome, in the LSB-first order. `buf` must hold at least
+    /// [`serialized_size`] bytes.
+    ///
+    /// [`serialized_size`]: #method.serialized_size
+    pub(crate) fn serialize(&self, buf: &mut [u8]) {
+        let bits = self.bits();
+        let mut bytes = buf.iter_mut();
+        let mut pending = 0u128;
+        let mut pending_bits = 0;
+
+        for &syndrome in &self.syndromes {
+            pending |= u128::from(syndrome) << pending_bits;
+            pending_bits += bits;
+            while pending_bits >= 8 {
+                *bytes.next().unwrap() = pending as u8;
+                pending >>= 8;
+                pending_bits -= 8;
+            }
+        }
+        if pending_bits > 0 {
+            *bytes.next().unwrap() = pending as u8;
+        }
+    }
+
+    /// Reads `bits` bits of every syndrome, in the LSB-first order. `buf` must hold at least
+    /// [`serialized_size`] bytes.
+    ///
+    /// [`serialized_size`]: #method.serialized_size
+    pub(crate) fn deserialize(&mut self, buf: &[u8]) {
+        let bits = self.bits();
+        let mut bytes = buf.iter();
+        let mut pending = 0u128;
+        let mut pending_bits = 0;
+
+        for syndrome in &mut self.syndromes {
+            while pending_bits < bits {
+                pending |= u128::from(*bytes.next().unwrap()) << pending_bits;
+                pending_bits += 8;
+            }
+            *syndrome = self.field.truncate(pending as u64);
+            pending >>= bits;
+            pending_bits -= bits;
+        }
+    }
+
+    /// Returns the power sum `s_k` for `k` from 1 to `2c`, squaring an odd one if `k` is even.
+    fn syndrome(&self, k: usize) -> u64 {
+        let squarings = k.trailing_zeros();
+        let odd = k >> squarings;
+
+        (0..squarings).fold(self.syndromes[odd / 2], |s, _| self.field.sqr(s))
+    }
+
+    /// Heap-free [`berlekamp_massey`] with `max_degree = N`. Writes the coefficients of `x^1` to
+    /// `x^d` of the locator into `locator`, and returns its degree `d`.
+    ///
+    /// [`berlekamp_massey`]: ../decoder/fn.berlekamp_massey.html
+    fn berlekamp_massey_array<const N: usize>(&self, current: &mut [u64; N]) -> Option<usize> {
+        let field = &self.field;
+        let mut prev = [0; N];
+        let (mut degree, mut prev_degree) = (0, 0);
+        let mut prev_discrepancy = 1;
+
+        for n in 0..2 * self.capacity() {
+            let discrepancy = (1..=degree).fold(self.syndrome(n + 1), |discrepancy, i| {
+                discrepancy ^ field.mul(self.syndrome(n + 1 - i), current[i - 1])
+            });
+            if discrepancy == 0 {
+                continue;
+            }
+
+            // Distance from the step at which `prev` was current
+            let shift = n + 1 - degree - prev_degree;
+            let replaced = if 2 * degree <= n {
+                if prev_degree + shift > N {
+                    return None;
+                }
+                Some((*current, degree))
+            } else {
+                None
+            };
+
+            // Constant term of `prev` is 1, and it isn't stored
+            let factor = field.mul(discrepancy, field.inv(prev_discrepancy));
+            current[shift - 1] ^= factor;
+            for i in 1..=prev_degree {
+                current[i + shift - 1] ^= field.mul(factor, prev[i - 1]);
+            }
+
+            if let Some((replaced, replaced_degree)) = replaced {
+                degree = prev_degree + shift;
+                prev = replaced;
+                prev_degree = replaced_degree;
+                prev_discrepancy = discrepancy;
+            }
+        }
+
+        if degree > 0 && current[degree - 1] == 0 {
+            return None;
+        }
+
+        Some(degree)
+    }
+
+    /// Returns power sums `s_1, s_2, ..., s_2c`, reconstructing even ones from odd ones.
+    fn all_syndromes(&self) -> Vec<u64> {
+        let mut all = vec![0; 2 * self.capacity()];
+        for (i, &syndrome) in self.syndromes.iter().enumerate() {
+            all[2 * i] = syndrome;
+        }
+        // s_(2i + 2) = s_(i + 1)^2, where s_(i + 1) is already known
+        for i in 0..self.capacity() {
+            all[2 * i + 1] = self.field.sqr(all[i]);
+        }
+
+        all
+    }
+}
+
+/// Returns all roots of a monic polynomial, or `None` unless it has as many distinct roots as
+/// its degree.
+fn find_roots(field: &Field, poly: &[u64]) -> Option<Vec<u64>> {
+    let degree = poly.len() - 1;
+    let mut roots = Vec::with_capacity(degree);
+    if degree == 0 {
+        return Some(roots);
+    }
+
+    // x^(2^bits) - x is the product of (x - a) over all field elements, so the polynomial
+    // divides it, i.e. x^(2^bits) = x modulo the polynomial, iff its roots are distinct and in
+    // the field
+    let x = poly_rem(field, vec![0, 1], poly);
+    let mut power = x.clone();
+    for _ in 0..field.bits() {
+        power = poly_sqr_rem(field, &power, poly);
+    }
+    if power != x {
+        return None;
+    }
+
+    split_roots(field, poly.to_vec(), 0, &mut roots);
+    Some(roots)
+}
+
+/// Collects roots of a monic polynomial with distinct roots, splitting it by traces of
+/// `b * x` for basis elements `b = 2^i`, starting from `i = basis`.
+///
+/// Trace is a linear map onto `{0, 1}`, and every two distinct roots have different traces for
+/// some basis element. The gcd with `Tr(b * x)` holds the roots of zero trace, so it splits the
+/// polynomial unless all roots have the same trace, which then holds for both factors as well.
+fn split_roots(field: &Field, poly: Vec<u64>, basis: u32, roots: &mut Vec<u64>) {
+    if poly.len() == 2 {
+        roots.push(poly[0]);
+        return;
+    }
+
+    for basis in basis..field.bits() {
+        let trace = poly_trace_rem(field, 1 << basis, &poly);
+        let factor = poly_gcd(field, poly.clone(), trace);
+
+        if factor.len() > 1 && factor.len() < poly.len() {
+            let quotient = poly_div(field, &poly, &factor);
+            split_roots(field, factor, basis + 1, roots);
+            split_roots(field, quotient, basis + 1, roots);
+            return;
+        }
+    }
+
+    unreachable!("Distinct roots are always split by some basis element");
+}
+
+/// Heap-free [`find_roots`] for a monic polynomial of `degree` up to `N`, stored without its
+/// leading coefficient. Writes the roots into `roots` in the same order.
+///
+/// Factors are split depth-first like in [`split_roots`], with a stack in place of recursion.
+/// Factors on the stack have distinct roots, so their coefficients fit into `poly` side by side.
+///
+/// [`find_roots`]: fn.find_roots.html
+/// [`split_roots`]: fn.split_roots.html
+fn find_roots_array<const N: usize>(
+    field: &Field,
+    mut poly: [u64; N],
+    degree: usize,
+    roots: &mut [u64; N],
+) -> Option<()> {
+    if degree == 0 {
+        return Some(());
+    }
+
+    // Same check for distinct roots in the field, x^(2^bits) = x modulo the polynomial
+    if degree > 1 {
+        let mut x = [0; N];
+        x[1] = 1;
+        let mut power = x;
+        for _ in 0..field.bits() {
+            power = array_sqr_rem(field, &power, &poly[..degree]);
+        }
+        if power[..degree] != x[..degree] {
+            return None;
+        }
+    }
+
+    // Offset and degree of a factor in `poly`, and the basis element to split it with
+    let mut stack = [(0, 0, 0); N];
+    stack[0] = (0, degree, 0);
+    let mut stack_len = 1;
+    let mut num_roots = 0;
+
+    while stack_len > 0 {
+        stack_len -= 1;
+        let (offset, degree, basis) = stack[stack_len];
+        if degree == 1 {
+            roots[num_roots] = poly[offset];
+            num_roots += 1;
+            continue;
+        }
+
+        let factor = &poly[offset..offset + degree];
+        let (split, gcd, gcd_degree) = (basis..field.bits())
+            .find_map(|basis| {
+                let trace = array_trace_rem::<N>(field, 1 << basis, factor);
+                array_split(field, factor, trace).map(|(gcd, gcd_degree)| (basis, gcd, gcd_degree))
+            })
+            .expect("Distinct roots are always split by some basis element");
+        let quotient = array_div::<N>(field, factor, &gcd[..=gcd_degree]);
+
+        // Factor and quotient take the place of the polynomial, and the factor is split first
+        poly[offset..offset + gcd_degree].copy_from_slice(&gcd[..gcd_degree]);
+        poly[offset + gcd_degree..offset + degree]
+            .copy_from_slice(&quotient[..degree - gcd_degree]);
+        stack[stack_len] = (offset + gcd_degree, degree - gcd_degree, split + 1);
+        stack[stack_len + 1] = (offset, gcd_degree, split + 1);
+        stack_len += 2;
+    }
+
+    Some(())
+}
+
+/// Returns `a^2` modulo a monic polynomial of degree `m.len()` whose other coefficients are `m`,
+/// for `a` of a lower degree.
+fn array_sqr_rem<const N: usize>(field: &Field, a: &[u64; N], m: &[u64]) -> [u64; N] {
+    let degree = m.len();
+
+    // Coefficients of x^degree and above go to `high`, as the square doesn't fit into `N`
+    let mut low = [0; N];
+    let mut high = [0; N];
+    for (i, &coefficient) in a[..degree].iter().enumerate() {
+        let square = field.sqr(coefficient);
+        if 2 * i < degree {
+            low[2 * i] = square;
+        } else {
+            high[2 * i - degree] = square;
+        }
+    }
+
+    // x^(degree + i) = x^i * m(x), going down from the highest power
+    for i in (0..degree.saturating_sub(1)).rev() {
+        let coefficient = high[i];
+        if coefficient != 0 {
+            for (j, &m) in m.iter().enumerate() {
+                let product = field.mul(coefficient, m);
+                if i + j < degree {
+                    low[i + j] ^= product;
+                } else {
+                    high[i + j - degree] ^= product;
+                }
+            }
+        }
+    }
+
+    low
+}
+
+/// Returns `Tr(b * x)` modulo a monic polynomial of degree `m.len()`, at least 2, whose other
+/// coefficients are `m`.
+fn array_trace_rem<const N: usize>(field: &Field, b: u64, m: &[u64]) -> [u64; N] {
+    let mut power = [0; N];
+    power[1] = b;
+    let mut trace = power;
+    for _ in 1..field.bits() {
+        power = array_sqr_rem(field, &power, m);
+        for (t, &p) in trace.iter_mut().zip(&power) {
+            *t ^= p;
+        }
+    }
+
+    trace
+}
+
+/// Reduces the first `len` coefficients of `a` modulo a monic polynomial of degree `m.len()`
+/// whose other coefficients are `m`, and returns the length of the remainder.
+fn array_rem<const N: usize>(field: &Field, a: &mut [u64; N], len: usize, m: &[u64]) -> usize {
+    let degree = m.len();
+    for i in (degree..len).rev() {
+        let coefficient = a[i];
+        if coefficient != 0 {
+            a[i] = 0;
+            for (j, &m) in m.iter().enumerate() {
+                a[i - degree + j] ^= field.mul(coefficient, m);
+            }
+        }
+    }
+
+    array_len(a, len.min(degree))
+}
+
+/// Returns the length of the first `len` coefficients of `a` without leading zeros.
+fn array_len(a: &[u64], mut len: usize) -> usize {
+    while len > 0 && a[len - 1] == 0 {
+        len -= 1;
+    }
+
+    len
+}
+
+/// Returns the monic greatest common divisor of a monic polynomial of degree `f.len()` whose
+/// other coefficients are `f` and a polynomial `b` of a lower degree, with its degree, if it's
+/// a proper factor.
+fn array_split<const N: usize>(field: &Field, f: &[u64], b: [u64; N]) -> Option<([u64; N], usize)> {
+    let degree = f.len();
+    let (mut a, mut b) = (b, [0; N]);
+    let mut a_len = array_len(&a, degree);
+    if a_len == 0 {
+        return None;
+    }
+    poly_make_monic(field, &mut a[..a_len]);
+
+    // The first remainder is of `f`, whose leading coefficient isn't stored
+    let a_degree = a_len - 1;
+    b[..degree].copy_from_slice(f);
+    for (b, &a) in b[degree - a_degree..degree].iter_mut().zip(&a[..a_degree]) {
+        *b ^= a;
+    }
+    let mut b_len = array_rem(field, &mut b, degree, &a[..a_degree]);
+
+    while b_len > 0 {
+        poly_make_monic(field, &mut b[..b_len]);
+        a_len = array_rem(field, &mut a, a_len, &b[..b_len - 1]);
+        core::mem::swap(&mut a, &mut b);
+        core::mem::swap(&mut a_len, &mut b_len);
+    }
+
+    let gcd_degree = a_len - 1;
+    if gcd_degree == 0 {
+        return None;
+    }
+
+    Some((a, gcd_degree))
+}
+
+/// Divides a monic polynomial of degree `f.len()` whose other coefficients are `f` by a monic
+/// polynomial `m` that divides it, and returns the quotient without its leading coefficient.
+fn array_div<const N: usize>(field: &Field, f: &[u64], m: &[u64]) -> [u64; N] {
+    let (degree, m_degree) = (f.len(), m.len() - 1);
+    let mut remainder = [0; N];
+    remainder[..degree].copy_from_slice(f);
+    let mut quotient = [0; N];
+
+    for i in (0..=degree - m_degree).rev() {
+        let coefficient = if i + m_degree == degree {
+            1
+        } else {
+            remainder[i + m_degree]
+        };
+        if i < degree - m_degree {
+            quotient[i] = coefficient;
+        }
+        if coefficient != 0 {
+            for (j, &m) in m[..m_degree].iter().enumerate() {
+                remainder[i + j] ^= field.mul(coefficient, m);
+            }
+        }
+    }
+
+    quotient
+}
+
+/// Removes leading zero coefficients, so that the zero polynomial is empty.
+fn poly_trim(poly: &mut Vec<u64>) {
+    while poly.last() == Some(&0) {
+        let _ = poly.pop();
+    }
+}
+
+/// Returns `a` modulo a monic polynomial `m`.
+fn poly_rem(field: &Field, mut a: Vec<u64>, m: &[u64]) -> Vec<u64> {
+    let degree = m.len() - 1;
+    for i in (degree..a.len()).rev() {
+        let coefficient = a[i];
+        if coefficient != 0 {
+            for (j, &m) in m[..degree].iter().enumerate() {
+                a[i - degree + j] ^= field.mul(coefficient, m);
+            }
+        }
+    }
+    a.truncate(degree);
+    poly_trim(&mut a);
+
+    a
+}
+
+/// Returns `a^2` modulo a monic polynomial `m`.
+fn poly_sqr_rem(field: &Field, a: &[u64], m: &[u64]) -> Vec<u64> {
+    // Squaring is linear in characteristic 2, so only the coefficients are squared
+    let mut square = vec![0; (2 * a.len()).saturating_sub(1)];
+    for (i, &coefficient) in a.iter().enumerate() {
+        square[2 * i] = field.sqr(coefficient);
+    }
+
+    poly_rem(field, square, m)
+}
+
+/// Returns `Tr(b * x) = (b * x) + (b * x)^2 + ... + (b * x)^(2^(bits - 1))` modulo a monic
+/// polynomial `m` of degree at least 2.
+fn poly_trace_rem(field: &Field, b: u64, m: &[u64]) -> Vec<u64> {
+    let mut power = vec![0, b];
+    let mut trace = power.clone();
+    for _ in 1..field.bits() {
+        power = poly_sqr_rem(field, &power, m);
+        trace.resize(trace.len().max(power.len()), 0);
+        for (t, &p) in trace.iter_mut().zip(&power) {
+            *t ^= p;
+        }
+    }
+    poly_trim(&mut trace);
+
+    trace
+}
+
+/// Makes a nonzero polynomial monic.
+fn poly_make_monic(field: &Field, poly: &mut [u64]) {
+    let inverse = field.inv(*poly.last().unwrap());
+    for coefficient in poly.iter_mut() {
+        *coefficient = field.mul(*coefficient, inverse);
+    }
+}
+
+/// Returns the monic greatest common divisor of a nonzero polynomial `a` and `b`.
+fn poly_gcd(field: &Field, mut a: Vec<u64>, mut b: Vec<u64>) -> Vec<u64> {
+    poly_make_monic(field, &mut a);
+    while !b.is_empty() {
+        poly_make_monic(field, &mut b);
+        let remainder = poly_rem(field, a, &b);
+        a = b;
+        b = remainder;
+    }
+
+    a
+}
+
+/// Divides `a` by a monic polynomial `m` that divides it.
+fn poly_div(field: &Field, a: &[u64], m: &[u64]) -> Vec<u64> {
+    let degree = m.len() - 1;
+    let mut remainder = a.to_vec();
+    let mut quotient = vec![0; a.len() - degree];
+
+    for i in (0..quotient.len()).rev() {
+        let coefficient = remainder[i + degree];
+        quotient[i] = coefficient;
+        if coefficient != 0 {
+            for (j, &m) in m.iter().enumerate() {
+                remainder[i + j] ^= field.mul(coefficient, m);
+            }
+        }
+    }
+
+    quotient
+}
@@ -0,0 +1,116 @@
+use crate::{Minisketch, MinisketchError};
+use alloc::vec::Vec;
+
+/// Sketch wrapper that allows putting 0 into a sketch by offsetting elements by one.
+///
+/// Sketches can only hold nonzero elements, from 1 to 2<sup>bits</sup> - 1. This wrapper maps
+/// elements from 0 to 2<sup>bits</sup> - 2 onto them by adding 1 to every element on insertion,
+/// and subtracting 1 from every element on decoding.
+///
+/// Both sides of reconciliation must use the wrapper, since the underlying sketch holds offset elements.
+///
+/// # Examples
+///
+/// ```rust
+/// use minisketch_rs::{Minisketch, OffsetSketch};
+/// let mut sketch_a = OffsetSketch::new(Minisketch::try_new(12, 0, 4)?);
+/// sketch_a.try_add(0)?;
+/// sketch_a.try_add(42)?;
+///
+/// let mut sketch_b = OffsetSketch::new(Minisketch::try_new(12, 0, 4)?);
+/// sketch_b.try_add(42)?;
+///
+/// sketch_a.merge(&sketch_b)?;
+/// assert_eq!(sketch_a.decode_to_vec()?, vec![0]);
+/// # Ok::<(), minisketch_rs::MinisketchError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct OffsetSketch {
+    inner: Minisketch,
+}
+
+impl OffsetSketch {
+    /// Wraps a sketch. The sketch must be empty or hold only offset elements.
+    pub fn new(sketch: Minisketch) -> Self {
+        OffsetSketch { inner: sketch }
+    }
+
+    /// Adds an element from 0 to 2<sup>bits</sup> - 2 to a sketch.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::ElementOutOfRange)` if `element` is out of range.
+    /// In that case, the sketch is not modified.
+    pub fn try_add(&mut self, element: u64) -> Result<(), MinisketchError> {
+        let out_of_range = MinisketchError::ElementOutOfRange {
+            element,
+            bits: self.inner.bits(),
+        };
+
+        match element.checked_add(1) {
+            Some(offset) => self.inner.try_add(offset).map_err(|_| out_of_range),
+            None => Err(out_of_range),
+        }
+    }
+
+    /// Merges the elements of another offset sketch into this sketch.
+    ///
+    /// See [`Minisketch::merge`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::IncompatibleMerge)` if sketches differ in their element size
+    /// or implementation.
+    ///
+    /// [`Minisketch::merge`]: struct.Minisketch.html#method.merge
+    pub fn merge(&mut self, other: &Self) -> Result<usize, MinisketchError> {
+        self.inner.merge(&other.inner)
+    }
+
+    /// Decodes the sketch into a newly allocated vector of original elements in arbitrary order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::DecodeFailed)` if decoding failed.
+    pub fn decode_to_vec(&self) -> Result<Vec<u64>, MinisketchError> {
+        let mut elements = self.inner.decode_to_vec()?;
+        for element in elements.iter_mut() {
+            *element -= 1;
+        }
+
+        Ok(elements)
+    }
+
+    /// Decodes the sketch into a newly allocated vector of original elements in ascending order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::DecodeFailed)` if decoding failed.
+    pub fn decode_sorted(&self) -> Result<Vec<u64>, MinisketchError> {
+        let mut elements = self.decode_to_vec()?;
+        elements.sort_unstable();
+
+        Ok(elements)
+    }
+
+    /// Returns a reference to the underlying sketch, e.g. to serialize it.
+    pub fn as_minisketch(&self) -> &Minisketch {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying sketch, e.g. to deserialize into it.
+    pub fn as_minisketch_mut(&mut self) -> &mut Minisketch {
+        &mut self.inner
+    }
+
+    /// Converts into the underlying sketch that holds offset elements.
+    pub fn into_minisketch(self) -> Minisketch {
+        self.inner
+    }
+}
+
+impl From<Minisketch> for OffsetSketch {
+    fn from(sketch: Minisketch) -> Self {
+        OffsetSketch::new(sketch)
+    }
+}
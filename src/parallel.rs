@@ -0,0 +1,65 @@
+//! Parallel decoding of many sketches on the `rayon` thread pool.
+
+use crate::{Minisketch, MinisketchError};
+use rayon::prelude::*;
+
+impl Minisketch {
+    /// Decodes many sketches in parallel.
+    ///
+    /// Returns the result of [`decode_to_vec`] for every sketch, in the same order as `sketches`.
+    ///
+    /// Only available with the `rayon` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::{Minisketch, SketchParams};
+    /// let params = SketchParams::new(12, 0, 2)?;
+    /// let sketches = (1..=3)
+    ///     .map(|i| Minisketch::from_iter_with(params, 1..=i))
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    ///
+    /// let results = Minisketch::decode_many(&sketches);
+    /// assert_eq!(results[0], Ok(vec![1]));
+    /// // Third sketch holds more elements than its capacity
+    /// assert!(results[2].is_err());
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    ///
+    /// [`decode_to_vec`]: struct.Minisketch.html#method.decode_to_vec
+    pub fn decode_many(sketches: &[Minisketch]) -> Vec<Result<Vec<u64>, MinisketchError>> {
+        sketches.par_iter().map(Minisketch::decode_to_vec).collect()
+    }
+
+    /// Merges every `(local, remote)` pair of sketches and decodes the result, in parallel.
+    ///
+    /// Returns the elements that are in only one of the sketches of every pair, in the same
+    /// order as `pairs`. Neither of the sketches is modified.
+    ///
+    /// Only available with the `rayon` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use minisketch_rs::{Minisketch, SketchParams};
+    /// let params = SketchParams::new(12, 0, 4)?;
+    /// let local = Minisketch::from_iter_with(params, 1..=10)?;
+    /// let remotes = (0..3)
+    ///     .map(|i| Minisketch::from_iter_with(params, 1..=10 + i))
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    ///
+    /// let pairs = remotes.iter().map(|remote| (&local, remote)).collect::<Vec<_>>();
+    /// let results = Minisketch::reconcile_many(&pairs);
+    /// assert_eq!(results[0], Ok(vec![]));
+    /// assert_eq!(results[1], Ok(vec![11]));
+    /// # Ok::<(), minisketch_rs::MinisketchError>(())
+    /// ```
+    pub fn reconcile_many(
+        pairs: &[(&Minisketch, &Minisketch)],
+    ) -> Vec<Result<Vec<u64>, MinisketchError>> {
+        pairs
+            .par_iter()
+            .map(|(local, remote)| local.try_xor(remote)?.decode_to_vec())
+            .collect()
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{Minisketch, MinisketchError};
+use core::fmt::{Display, Formatter};
+
+/// Parameters of a sketch: element size, implementation and capacity.
+///
+/// Parameters are validated on construction against what `libminisketch` was compiled with,
+/// see [`validate`].
+///
+/// # Examples
+///
+/// ```rust
+/// use minisketch_rs::SketchParams;
+/// let params = SketchParams::builder().bits(32).capacity(16).build()?;
+/// assert_eq!(params.to_string(), "bits = 32, implementation = 0, capacity = 16");
+///
+/// let mut sketch = params.create_sketch()?;
+/// sketch.add(42);
+/// assert_eq!(sketch.params(), params);
+/// # Ok::<(), minisketch_rs::MinisketchError>(())
+/// ```
+///
+/// [`validate`]: struct.SketchParams.html#method.validate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SketchParams {
+    bits: u32,
+    implementation: u32,
+    capacity: usize,
+}
+
+impl SketchParams {
+    /// Creates validated sketch parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::UnsupportedParameters)` if validation fails, see [`validate`].
+    ///
+    /// [`validate`]: struct.SketchParams.html#method.validate
+    pub fn new(bits: u32, implementation: u32, capacity: usize) -> Result<Self, MinisketchError> {
+        let params = SketchParams {
+            bits,
+            implementation,
+            capacity,
+        };
+        params.validate()?;
+
+        Ok(params)
+    }
+
+    /// Reads parameters of an existing sketch, which are valid by construction.
+    pub(crate) fn from_sketch(sketch: &Minisketch) -> Self {
+        SketchParams {
+            bits: sketch.bits(),
+            implementation: sketch.implementation(),
+            capacity: sketch.capacity(),
+        }
+    }
+
+    /// Returns a builder of sketch parameters.
+    pub fn builder() -> SketchParamsBuilder {
+        SketchParamsBuilder::default()
+    }
+
+    /// Returns element size in bits.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Returns implementation number.
+    pub fn implementation(&self) -> u32 {
+        self.implementation
+    }
+
+    /// Returns capacity in number of elements.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the size in bytes of a serialized sketch with these parameters.
+    pub fn serialized_size(&self) -> usize {
+        Minisketch::serialized_size_for(self.bits, self.capacity)
+    }
+
+    /// Checks that support for `bits` was compiled into `libminisketch`, that `implementation`
+    /// does not exceed [`Minisketch::implementation_max`] and that `capacity` is not 0.
+    ///
+    /// Not every combination of element size and implementation exists, so creating a sketch
+    /// with valid parameters may still fail.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::UnsupportedParameters)` if any of the checks fails.
+    ///
+    /// [`Minisketch::implementation_max`]: struct.Minisketch.html#method.implementation_max
+    pub fn validate(&self) -> Result<(), MinisketchError> {
+        if Minisketch::bits_supported(self.bits)
+            && self.implementation <= Minisketch::implementation_max()
+            && self.capacity > 0
+        {
+            Ok(())
+        } else {
+            Err(MinisketchError::UnsupportedParameters {
+                bits: self.bits,
+                implementation: self.implementation,
+                capacity: self.capacity,
+            })
+        }
+    }
+
+    /// Returns `true` if sketches with these and `other` parameters can be merged, i.e. they have
+    /// the same element size and implementation. Capacities may differ.
+    pub fn is_mergeable_with(&self, other: &SketchParams) -> bool {
+        self.bits == other.bits && self.implementation == other.implementation
+    }
+
+    /// Tries to create a new empty sketch with these parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::UnsupportedParameters)` if the combination of element size and
+    /// implementation is unavailable.
+    pub fn create_sketch(&self) -> Result<Minisketch, MinisketchError> {
+        Minisketch::try_new(self.bits, self.implementation, self.capacity)
+    }
+}
+
+impl Display for SketchParams {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), core::fmt::Error> {
+        write!(
+            f,
+            "bits = {}, implementation = {}, capacity = {}",
+            self.bits, self.implementation, self.capacity
+        )
+    }
+}
+
+/// Builder of [`SketchParams`].
+///
+/// Defaults to 64-bit elements, implementation 0 and capacity of 1 element.
+///
+/// # Examples
+///
+/// ```rust
+/// use minisketch_rs::SketchParams;
+/// // Capacity can be computed from the expected number of elements and false positive rate
+/// let params = SketchParams::builder()
+///     .bits(32)
+///     .max_elements(10, 32)
+///     .build()?;
+/// assert_eq!(params.capacity(), 11);
+/// # Ok::<(), minisketch_rs::MinisketchError>(())
+/// ```
+///
+/// [`SketchParams`]: struct.SketchParams.html
+#[derive(Debug, Clone)]
+pub struct SketchParamsBuilder {
+    bits: u32,
+    implementation: u32,
+    capacity: Capacity,
+}
+
+#[derive(Debug, Clone)]
+enum Capacity {
+    Exact(usize),
+    FalsePositiveRate { max_elements: usize, fpbits: u32 },
+}
+
+impl Default for SketchParamsBuilder {
+    fn default() -> Self {
+        SketchParamsBuilder {
+            bits: 64,
+            implementation: 0,
+            capacity: Capacity::Exact(1),
+        }
+    }
+}
+
+impl SketchParamsBuilder {
+    /// Sets element size in bits.
+    pub fn bits(mut self, bits: u32) -> Self {
+        self.bits = bits;
+        self
+    }
+
+    /// Sets implementation number.
+    pub fn implementation(mut self, implementation: u32) -> Self {
+        self.implementation = implementation;
+        self
+    }
+
+    /// Sets capacity in number of elements.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Capacity::Exact(capacity);
+        self
+    }
+
+    /// Sets capacity that allows decoding up to `max_elements` elements with a false positive
+    /// probability of at most `1 / 2^fpbits`, see [`Minisketch::compute_capacity`].
+    ///
+    /// [`Minisketch::compute_capacity`]: struct.Minisketch.html#method.compute_capacity
+    pub fn max_elements(mut self, max_elements: usize, fpbits: u32) -> Self {
+        self.capacity = Capacity::FalsePositiveRate {
+            max_elements,
+            fpbits,
+        };
+        self
+    }
+
+    /// Builds validated sketch parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::UnsupportedParameters)` if validation fails,
+    /// see [`SketchParams::validate`].
+    ///
+    /// [`SketchParams::validate`]: struct.SketchParams.html#method.validate
+    pub fn build(self) -> Result<SketchParams, MinisketchError> {
+        let capacity = match self.capacity {
+            Capacity::Exact(capacity) => capacity,
+            Capacity::FalsePositiveRate {
+                max_elements,
+                fpbits,
+            } => Minisketch::compute_capacity(self.bits, max_elements, fpbits),
+        };
+
+        SketchParams::new(self.bits, self.implementation, capacity)
+    }
+}
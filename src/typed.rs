@@ -0,0 +1,363 @@
+//! Sketches with element size and capacity fixed at compile time.
+//!
+//! [`Sketch`] wraps [`Minisketch`], but takes its element size `BITS` and `CAPACITY` as const
+//! parameters. This allows to:
+//!
+//! * reject unsupported element sizes at compile time;
+//! * represent elements with the smallest unsigned integer type that fits them (`u16` for up to
+//!   16 bits, `u32` for up to 32 bits and `u64` for up to 64 bits) and refuse elements that are
+//!   zero or wider than `BITS` instead of silently dropping or truncating them;
+//! * refuse merging sketches of different sizes at compile time;
+//! * serialize sketches into fixed-size arrays.
+//!
+//! [`Sketch`]: struct.Sketch.html
+//! [`Minisketch`]: ../struct.Minisketch.html
+
+use crate::{Minisketch, MinisketchError};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt::Debug;
+use core::iter::FromIterator;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Marker type for element size of `N` bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Bits<const N: u32>;
+
+/// Element sizes that are available for typed sketches, from 2 to 64 bits.
+///
+/// This trait is sealed and can't be implemented outside of this crate.
+pub trait SupportedBits: sealed::Sealed {
+    /// Smallest unsigned integer type that holds an element of this size.
+    type Element: Element;
+}
+
+/// Unsigned integer type that holds elements of a typed sketch.
+///
+/// This trait is sealed and can't be implemented outside of this crate.
+pub trait Element: sealed::Sealed + Copy + Debug + Ord + Into<u64> {
+    /// Converts a decoded `u64` element that is known to fit into this type.
+    #[doc(hidden)]
+    fn from_u64(value: u64) -> Self;
+}
+
+macro_rules! impl_element {
+    ($($t:ty)*) => {
+        $(
+            impl sealed::Sealed for $t {}
+            impl Element for $t {
+                fn from_u64(value: u64) -> Self {
+                    value as $t
+                }
+            }
+        )*
+    };
+}
+
+impl_element!(u16 u32 u64);
+
+macro_rules! impl_supported_bits {
+    ($t:ty => $($n:literal)*) => {
+        $(
+            impl sealed::Sealed for Bits<$n> {}
+            impl SupportedBits for Bits<$n> {
+                type Element = $t;
+            }
+        )*
+    };
+}
+
+impl_supported_bits!(u16 => 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16);
+impl_supported_bits!(u32 => 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32);
+impl_supported_bits!(u64 => 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48
+                            49 50 51 52 53 54 55 56 57 58 59 60 61 62 63 64);
+
+/// Shorthand for the element type of a sketch with `BITS`-bit elements.
+pub type ElementOf<const BITS: u32> = <Bits<BITS> as SupportedBits>::Element;
+
+/// Compile-time assertions on const parameters that can't be expressed with trait bounds.
+struct Check<const BITS: u32, const CAPACITY: usize, const N: usize>;
+
+impl<const BITS: u32, const CAPACITY: usize, const N: usize> Check<BITS, CAPACITY, N> {
+    const CAPACITY_NONZERO: () = assert!(CAPACITY > 0, "Sketch capacity must be nonzero");
+    const SERIALIZED_SIZE: () = assert!(
+        N == (BITS as usize * CAPACITY).div_ceil(8),
+        "Array length must be equal to the serialized size of the sketch"
+    );
+}
+
+/// Sketch of `BITS`-bit elements with capacity of `CAPACITY` elements.
+///
+/// # Examples
+///
+/// ```rust
+/// use minisketch_rs::Sketch;
+///
+/// let mut sketch_a = Sketch::<12, 4>::new()?;
+/// sketch_a.add(10)?;
+/// sketch_a.add(43)?;
+///
+/// let mut sketch_b = Sketch::<12, 4>::new()?;
+/// sketch_b.add(42)?;
+/// sketch_b.add(43)?;
+///
+/// // Serialized size is known at compile time
+/// let message: [u8; 6] = sketch_b.to_bytes();
+/// let sketch_b = Sketch::<12, 4>::from_bytes(&message)?;
+///
+/// sketch_a.merge(&sketch_b)?;
+/// let differences: Vec<u16> = sketch_a.decode_sorted()?;
+/// assert_eq!(differences, vec![10, 42]);
+///
+/// // Elements that don't fit into 12 bits are rejected, as well as 0
+/// assert!(sketch_a.add(1 << 12).is_err());
+/// assert!(sketch_a.add(0).is_err());
+/// # Ok::<(), minisketch_rs::MinisketchError>(())
+/// ```
+///
+/// Unsupported element sizes don't compile:
+///
+/// ```compile_fail
+/// use minisketch_rs::Sketch;
+/// let sketch = Sketch::<65, 4>::new();
+/// ```
+///
+/// Neither does merging sketches of different sizes:
+///
+/// ```compile_fail
+/// use minisketch_rs::Sketch;
+/// let mut sketch_a = Sketch::<32, 4>::new().unwrap();
+/// let sketch_b = Sketch::<64, 4>::new().unwrap();
+/// sketch_a.merge(&sketch_b);
+/// ```
+///
+/// Nor serializing into an array of the wrong size:
+///
+/// ```compile_fail
+/// use minisketch_rs::Sketch;
+/// let sketch = Sketch::<12, 4>::new().unwrap();
+/// let message: [u8; 5] = sketch.to_bytes();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Sketch<const BITS: u32, const CAPACITY: usize>
+where
+    Bits<BITS>: SupportedBits,
+{
+    inner: Minisketch,
+}
+
+impl<const BITS: u32, const CAPACITY: usize> Sketch<BITS, CAPACITY>
+where
+    Bits<BITS>: SupportedBits,
+{
+    /// Size in bytes of the serialized representation of the sketch.
+    pub const SERIALIZED_SIZE: usize = (BITS as usize * CAPACITY).div_ceil(8);
+
+    /// Tries to create a new empty sketch with the default implementation (0).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::UnsupportedParameters)` if support for `BITS`-bit elements
+    /// wasn't compiled into `libminisketch`.
+    pub fn new() -> Result<Self, MinisketchError> {
+        Self::with_implementation(0)
+    }
+
+    /// Tries to create a new empty sketch with given `implementation`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::UnsupportedParameters)` if the combination of `BITS` and
+    /// `implementation` is unavailable.
+    pub fn with_implementation(implementation: u32) -> Result<Self, MinisketchError> {
+        let () = Check::<BITS, CAPACITY, 0>::CAPACITY_NONZERO;
+
+        Ok(Sketch {
+            inner: Minisketch::try_new(BITS, implementation, CAPACITY)?,
+        })
+    }
+
+    /// Tries to create a sketch with the default implementation from its serialized representation.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::UnsupportedParameters)` if support for `BITS`-bit elements
+    /// wasn't compiled into `libminisketch`.
+    pub fn from_bytes<const N: usize>(buf: &[u8; N]) -> Result<Self, MinisketchError> {
+        let () = Check::<BITS, CAPACITY, N>::SERIALIZED_SIZE;
+
+        let mut sketch = Self::new()?;
+        sketch.inner.deserialize(buf)?;
+
+        Ok(sketch)
+    }
+
+    /// Serializes the sketch into an array of [`SERIALIZED_SIZE`] bytes.
+    ///
+    /// Using an array of any other size fails to compile.
+    ///
+    /// [`SERIALIZED_SIZE`]: #associatedconstant.SERIALIZED_SIZE
+    pub fn to_bytes<const N: usize>(&self) -> [u8; N] {
+        let () = Check::<BITS, CAPACITY, N>::SERIALIZED_SIZE;
+
+        let mut buf = [0u8; N];
+        self.inner
+            .serialize(&mut buf)
+            .expect("Array size is checked at compile time");
+
+        buf
+    }
+
+    /// Adds an element to the sketch.
+    ///
+    /// Note that adding the same element a second time removes it again, as sketches have
+    /// set semantics, not multiset semantics.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::ElementOutOfRange)` if `element` is 0 or doesn't fit into
+    /// `BITS` bits. In that case, the sketch is not modified.
+    pub fn add(&mut self, element: ElementOf<BITS>) -> Result<(), MinisketchError> {
+        self.inner.try_add(element.into())
+    }
+
+    /// Merges the elements of another sketch of the same size into this sketch.
+    ///
+    /// See [`Minisketch::merge`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::IncompatibleMerge)` if sketches have different implementations.
+    ///
+    /// [`Minisketch::merge`]: ../struct.Minisketch.html#method.merge
+    pub fn merge(&mut self, other: &Self) -> Result<(), MinisketchError> {
+        let _ = self.inner.merge(&other.inner)?;
+        Ok(())
+    }
+
+    /// Decodes the sketch into a newly allocated vector of elements in arbitrary order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::DecodeFailed)` if decoding failed.
+    pub fn decode_to_vec(&self) -> Result<Vec<ElementOf<BITS>>, MinisketchError> {
+        Ok(self
+            .inner
+            .decode_to_vec()?
+            .into_iter()
+            .map(Element::from_u64)
+            .collect())
+    }
+
+    /// Decodes the sketch into a newly allocated vector of elements in ascending order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::DecodeFailed)` if decoding failed.
+    pub fn decode_sorted(&self) -> Result<Vec<ElementOf<BITS>>, MinisketchError> {
+        let mut elements = self.decode_to_vec()?;
+        elements.sort_unstable();
+
+        Ok(elements)
+    }
+
+    /// Decodes the sketch into an array of `CAPACITY` elements.
+    ///
+    /// Returns `Ok((elements, num. of decoded elements))`, only the first `num` elements of the
+    /// array are meaningful. Decoding allocates only without the `pure-rust` feature, see
+    /// [`Minisketch::decode_array`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinisketchError::DecodeFailed)` if decoding failed.
+    ///
+    /// [`Minisketch::decode_array`]: ../struct.Minisketch.html#method.decode_array
+    pub fn decode_array(&self) -> Result<([ElementOf<BITS>; CAPACITY], usize), MinisketchError> {
+        let (decoded, num_elements) = self.inner.decode_array::<CAPACITY>()?;
+
+        Ok((decoded.map(Element::from_u64), num_elements))
+    }
+
+    /// Set the seed for randomizing algorithm choices to a fixed value.
+    ///
+    /// See [`Minisketch::set_seed`] for details.
+    ///
+    /// [`Minisketch::set_seed`]: ../struct.Minisketch.html#method.set_seed
+    pub fn set_seed(&mut self, seed: u64) {
+        self.inner.set_seed(seed)
+    }
+
+    /// Returns implementation version number.
+    pub fn implementation(&self) -> u32 {
+        self.inner.implementation()
+    }
+
+    /// Returns a reference to the underlying untyped sketch.
+    pub fn as_minisketch(&self) -> &Minisketch {
+        &self.inner
+    }
+
+    /// Converts into the underlying untyped sketch.
+    pub fn into_minisketch(self) -> Minisketch {
+        self.inner
+    }
+}
+
+/// Creates a sketch with the default implementation that holds all elements of an iterator.
+///
+/// Elements are checked one by one, and added in batches with [`Minisketch::add_all`].
+///
+/// # Panics
+///
+/// Panics if support for `BITS`-bit elements wasn't compiled into `libminisketch`, or if any
+/// of the elements is 0 or doesn't fit into `BITS` bits.
+///
+/// # Example
+///
+/// ```rust
+/// use minisketch_rs::Sketch;
+/// let sketch: Sketch<12, 4> = vec![10u16, 42].into_iter().collect();
+/// assert_eq!(sketch.decode_sorted()?, vec![10, 42]);
+/// # Ok::<(), minisketch_rs::MinisketchError>(())
+/// ```
+///
+/// [`Minisketch::add_all`]: ../struct.Minisketch.html#method.add_all
+impl<const BITS: u32, const CAPACITY: usize> FromIterator<ElementOf<BITS>>
+    for Sketch<BITS, CAPACITY>
+where
+    Bits<BITS>: SupportedBits,
+{
+    fn from_iter<I: IntoIterator<Item = ElementOf<BITS>>>(iter: I) -> Self {
+        let mut sketch = Self::new().expect("Unsupported sketch parameters");
+        sketch.inner.extend(iter.into_iter().map(|element| {
+            let element = element.into();
+            Minisketch::check_element(BITS, element).expect("Invalid sketch element");
+            element
+        }));
+
+        sketch
+    }
+}
+
+/// Converts an untyped sketch into a typed one, checking its element size and capacity.
+impl<const BITS: u32, const CAPACITY: usize> TryFrom<Minisketch> for Sketch<BITS, CAPACITY>
+where
+    Bits<BITS>: SupportedBits,
+{
+    type Error = MinisketchError;
+
+    fn try_from(sketch: Minisketch) -> Result<Self, Self::Error> {
+        if sketch.bits() != BITS || sketch.capacity() != CAPACITY {
+            return Err(MinisketchError::UnsupportedParameters {
+                bits: sketch.bits(),
+                implementation: sketch.implementation(),
+                capacity: sketch.capacity(),
+            });
+        }
+
+        Ok(Sketch { inner: sketch })
+    }
+}